@@ -0,0 +1,106 @@
+use rig::{
+    agent::{Agent, AgentBuilder},
+    completion::{CompletionModel, ToolDefinition},
+    tool::{Tool, ToolSet},
+};
+
+use crate::{
+    balance::GetBalance,
+    chains::chains_context,
+    erc20_transfer::ERC20Transfer,
+    eth_transfer::ETHTransfer,
+    guard::Guarded,
+    llm::AgentSpec,
+    pending_tools::{CancelTx, ListPendingTxs, SpeedUpTx},
+    registry_tools::{ListChains, ListTokens},
+    swap::{EthSwapQuote, EthSwapToERC20},
+};
+
+pub const PREAMBLE: &str = "You are an on-chain assistant for EVM chains. \
+You can look up chains and tokens, check balances, quote and perform ETH to ERC20 swaps, \
+and transfer ETH or ERC20 tokens. Pick tools as follows:
+- list_chains, list_tokens: find chain names and token addresses. Never guess a token address.
+- get_balance: check ETH or token balances, e.g. before a transfer or swap.
+- eth_swap_quote: tell the user what a swap would return. Use it when asked for a price or quote.
+- eth_swap_to_erc20: swap ETH for an ERC20 token once the user asked to swap.
+- eth_transfer: send native ETH to an address.
+- erc20_transfer: send ERC20 tokens to an address.
+- list_pending_txs: list sent transactions that are not mined yet, and which are stuck.
+- speed_up_tx, cancel_tx: replace a pending transaction with a higher fee, or cancel it. \
+Only when the user asks, using a hash from list_pending_txs.
+Only send transactions the user asked for, one step at a time, and report each transaction hash.";
+
+pub const TOOL_NAMES: [&str; 10] = [
+    ListChains::NAME,
+    ListTokens::NAME,
+    GetBalance::NAME,
+    EthSwapQuote::NAME,
+    EthSwapToERC20::NAME,
+    ETHTransfer::NAME,
+    ERC20Transfer::NAME,
+    ListPendingTxs::NAME,
+    SpeedUpTx::NAME,
+    CancelTx::NAME,
+];
+
+/// Single agent with every tool registered, arguments are checked against the tool schemas.
+pub struct EthAgent;
+impl AgentSpec for EthAgent {
+    fn build<M: CompletionModel>(&self, builder: AgentBuilder<M>) -> Agent<M> {
+        builder
+            .preamble(PREAMBLE)
+            .context(&chains_context())
+            .tool(Guarded(ListChains))
+            .tool(Guarded(ListTokens))
+            .tool(Guarded(GetBalance))
+            .tool(Guarded(EthSwapQuote))
+            .tool(Guarded(EthSwapToERC20))
+            .tool(Guarded(ETHTransfer))
+            .tool(Guarded(ERC20Transfer))
+            .tool(Guarded(ListPendingTxs))
+            .tool(Guarded(SpeedUpTx))
+            .tool(Guarded(CancelTx))
+            .build()
+    }
+}
+
+/// The same tools as [`EthAgent`], for calling them without an LLM.
+pub fn toolset() -> ToolSet {
+    ToolSet::builder()
+        .static_tool(Guarded(ListChains))
+        .static_tool(Guarded(ListTokens))
+        .static_tool(Guarded(GetBalance))
+        .static_tool(Guarded(EthSwapQuote))
+        .static_tool(Guarded(EthSwapToERC20))
+        .static_tool(Guarded(ETHTransfer))
+        .static_tool(Guarded(ERC20Transfer))
+        .static_tool(Guarded(ListPendingTxs))
+        .static_tool(Guarded(SpeedUpTx))
+        .static_tool(Guarded(CancelTx))
+        .build()
+}
+
+/// Definitions of the tools in [`toolset`], in the order of [`TOOL_NAMES`].
+pub async fn tool_definitions() -> Vec<ToolDefinition> {
+    vec![
+        ListChains.definition(String::new()).await,
+        ListTokens.definition(String::new()).await,
+        GetBalance.definition(String::new()).await,
+        EthSwapQuote.definition(String::new()).await,
+        EthSwapToERC20.definition(String::new()).await,
+        ETHTransfer.definition(String::new()).await,
+        ERC20Transfer.definition(String::new()).await,
+        ListPendingTxs.definition(String::new()).await,
+        SpeedUpTx.definition(String::new()).await,
+        CancelTx.definition(String::new()).await,
+    ]
+}
+
+#[test]
+fn test_toolset_matches_tool_names() {
+    let toolset = toolset();
+    for name in TOOL_NAMES {
+        assert!(toolset.contains(name), "{} is not registered", name);
+        assert!(PREAMBLE.contains(name), "{} is not described", name);
+    }
+}
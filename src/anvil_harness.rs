@@ -0,0 +1,230 @@
+//! Test harness: a fresh Anvil node per test with Uniswap V2 deployed, written to
+//! a temporary chain config the tools are pointed at, so tool tests do not
+//! depend on a node running on :8545.
+//!
+//! The contracts are the compiled artifacts in `tests/fixtures/uniswap-v2`
+//! (`fetch.sh` there downloads them): the test ERC20, WETH9, UniswapV2Factory
+//! and UniswapV2Router02. The token is configured as the chain's USDC and gets
+//! an ETH pair with liquidity to swap against.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use alloy::{
+    hex,
+    network::{EthereumWallet, TransactionBuilder},
+    node_bindings::{Anvil, AnvilInstance},
+    primitives::{utils::parse_ether, Address, U256},
+    providers::{Provider, ProviderBuilder},
+    rpc::types::TransactionRequest,
+    sol,
+    sol_types::SolValue,
+    transports::http::{Client, Http},
+};
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+
+use crate::{
+    chains::add_chain_config, erc20_transfer::IERC20, nonce::NONCES, wallet::local_signer,
+};
+
+/// Liquidity the token/WETH pair starts with: 2000 tokens per ETH.
+const POOL_TOKENS: &str = "1000000";
+const POOL_ETH: &str = "500";
+
+sol! {
+    #[sol(rpc)]
+    interface IUniswapV2Router02 {
+        function addLiquidityETH(address token, uint amountTokenDesired, uint amountTokenMin, uint amountETHMin, address to, uint deadline) external payable returns (uint amountToken, uint amountETH, uint liquidity);
+        function getAmountsOut(uint amountIn, address[] memory path) external view returns (uint[] memory amounts);
+    }
+
+    #[sol(rpc)]
+    interface IUniswapV2Factory {
+        function getPair(address tokenA, address tokenB) external view returns (address pair);
+    }
+}
+
+/// Creation code of `tests/fixtures/uniswap-v2/<name>.json`, in the shapes Waffle,
+/// Hardhat and Foundry write.
+fn artifact(name: &str) -> Result<Vec<u8>> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/uniswap-v2")
+        .join(format!("{}.json", name));
+    let content = fs::read_to_string(&path).with_context(|| {
+        format!(
+            "read {}, run tests/fixtures/uniswap-v2/fetch.sh",
+            path.display()
+        )
+    })?;
+    let artifact: Value = serde_json::from_str(&content)?;
+    let bytecode = [
+        &artifact["bytecode"],
+        &artifact["bytecode"]["object"],
+        &artifact["evm"]["bytecode"]["object"],
+    ]
+    .into_iter()
+    .find_map(Value::as_str)
+    .ok_or_else(|| anyhow!("{}: no bytecode", path.display()))?;
+    Ok(hex::decode(bytecode)?)
+}
+
+/// A running Anvil node with the fixtures deployed. The node is killed and the
+/// temporary config removed when this is dropped.
+pub struct AnvilHarness {
+    pub anvil: AnvilInstance,
+    /// Name the node's chain is configured under.
+    pub chain: String,
+    pub config_path: PathBuf,
+    /// The test ERC20, configured as the chain's USDC.
+    pub token: Address,
+    pub weth: Address,
+    pub router: Address,
+    /// The token/WETH pair.
+    pub pair: Address,
+}
+
+impl AnvilHarness {
+    pub async fn spawn() -> Result<Self> {
+        let anvil = Anvil::new()
+            .try_spawn()
+            .map_err(|e| anyhow!("failed to spawn anvil, is it installed? {}", e))?;
+        let deployer = local_signer().address();
+        let provider = ProviderBuilder::new()
+            .with_recommended_fillers()
+            .wallet(EthereumWallet::from(local_signer()))
+            .on_http(anvil.endpoint_url());
+
+        let mut deployed = Vec::new();
+        let supply = parse_ether("10000000")?;
+        for (name, args) in [
+            ("ERC20", supply.abi_encode()),
+            ("WETH9", vec![]),
+            ("UniswapV2Factory", deployer.abi_encode()),
+        ] {
+            deployed.push(deploy(&provider, &[artifact(name)?, args].concat()).await?);
+        }
+        let [token, weth, factory] = deployed[..] else {
+            unreachable!()
+        };
+        let router = deploy(
+            &provider,
+            &[
+                artifact("UniswapV2Router02")?,
+                (factory, weth).abi_encode_params(),
+            ]
+            .concat(),
+        )
+        .await?;
+
+        // The router creates the pair on the first liquidity added.
+        IERC20::new(token, &provider)
+            .approve(router, U256::MAX)
+            .send()
+            .await?
+            .get_receipt()
+            .await?;
+        IUniswapV2Router02::new(router, &provider)
+            .addLiquidityETH(
+                token,
+                parse_ether(POOL_TOKENS)?,
+                U256::ZERO,
+                U256::ZERO,
+                deployer,
+                U256::MAX,
+            )
+            .value(parse_ether(POOL_ETH)?)
+            .send()
+            .await?
+            .get_receipt()
+            .await?;
+        let pair = IUniswapV2Factory::new(factory, &provider)
+            .getPair(token, weth)
+            .call()
+            .await?
+            .pair;
+
+        let chain = format!("anvil-{}", anvil.port());
+        let dir = std::env::temp_dir().join(format!("rig-eth-{}", chain));
+        fs::create_dir_all(&dir)?;
+        let config_path = dir.join("chains.json");
+        fs::write(
+            &config_path,
+            serde_json::to_string_pretty(&json!([{
+                "chain": chain,
+                "chain_id": anvil.chain_id(),
+                "provider_url": anvil.endpoint(),
+                "tokens": { "USDC": token.to_string(), "WETH": weth.to_string() },
+                "swap_router": router.to_string(),
+                "stablecoin": "USDC",
+            }]))?,
+        )?;
+        add_chain_config(&config_path)?;
+        // A new node may reuse the port of an earlier one: forget its nonces.
+        NONCES.resync(&chain, deployer);
+
+        Ok(AnvilHarness {
+            anvil,
+            chain,
+            config_path,
+            token,
+            weth,
+            router,
+            pair,
+        })
+    }
+
+    pub fn endpoint(&self) -> String {
+        self.anvil.endpoint()
+    }
+
+    /// Raw balance of the fixture token held by `owner`.
+    pub async fn token_balance(&self, owner: Address) -> Result<U256> {
+        let provider = ProviderBuilder::new().on_http(self.anvil.endpoint_url());
+        Ok(IERC20::new(self.token, provider)
+            .balanceOf(owner)
+            .call()
+            .await?
+            ._0)
+    }
+
+    pub async fn eth_balance(&self, owner: Address) -> Result<U256> {
+        let provider = ProviderBuilder::new().on_http(self.anvil.endpoint_url());
+        Ok(provider.get_balance(owner).await?)
+    }
+
+    /// Tokens the pair pays out for `amount` wei at its current reserves.
+    pub async fn quote(&self, amount: U256) -> Result<U256> {
+        let provider = ProviderBuilder::new().on_http(self.anvil.endpoint_url());
+        let amounts = IUniswapV2Router02::new(self.router, provider)
+            .getAmountsOut(amount, vec![self.weth, self.token])
+            .call()
+            .await?
+            .amounts;
+        amounts
+            .last()
+            .copied()
+            .ok_or(anyhow!("router quoted no amounts"))
+    }
+}
+
+async fn deploy<P: Provider<Http<Client>>>(provider: &P, code: &[u8]) -> Result<Address> {
+    let tx = TransactionRequest::default().with_deploy_code(code.to_vec());
+    let receipt = provider.send_transaction(tx).await?.get_receipt().await?;
+    if !receipt.status() {
+        return Err(anyhow!("fixture deployment reverted"));
+    }
+    receipt
+        .contract_address
+        .ok_or(anyhow!("fixture deployment has no contract address"))
+}
+
+impl Drop for AnvilHarness {
+    fn drop(&mut self) {
+        if let Some(dir) = self.config_path.parent() {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use std::{
+    fs::{self, OpenOptions},
+    future::Future,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use alloy::primitives::{Address, Bytes, B256};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{settings::audit_log_path, tx_output::TxStatus, users};
+
+/// Whether a tool call was let through to the tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    Allowed,
+    Rejected,
+    DryRun,
+}
+
+/// One line of the audit log: a single tool invocation and the transaction it sent, if any.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub prompt: Option<String>,
+    pub model: Option<String>,
+    /// User of the HTTP API the call was made for.
+    #[serde(default)]
+    pub user: Option<String>,
+    pub tool: String,
+    /// Arguments exactly as the model sent them.
+    pub arguments: Value,
+    pub decision: Decision,
+    pub error: Option<String>,
+    pub chain: Option<String>,
+    /// Account that signed the transaction.
+    pub account: Option<Address>,
+    pub signed_tx: Option<Bytes>,
+    pub tx_hash: Option<B256>,
+    pub status: Option<TxStatus>,
+}
+
+impl AuditRecord {
+    /// Start a record for a call, with the prompt and model of the surrounding [`scope`].
+    pub fn new(tool: &str, arguments: Value) -> Self {
+        let context = CONTEXT.try_with(|c| c.clone()).ok();
+        let now = Utc::now();
+        AuditRecord {
+            started_at: now,
+            finished_at: now,
+            prompt: context.as_ref().map(|c| c.prompt.clone()),
+            model: context.map(|c| c.model),
+            user: users::current().map(|user| user.name.clone()),
+            tool: tool.to_string(),
+            chain: arguments
+                .get("chain")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            arguments,
+            decision: Decision::Allowed,
+            error: None,
+            account: None,
+            signed_tx: None,
+            tx_hash: None,
+            status: None,
+        }
+    }
+
+    /// Fill in what the tool returned. Transaction outputs carry the canonical chain,
+    /// the signer and the transaction itself; other outputs leave those fields empty.
+    pub fn with_output(mut self, output: &Value) -> Self {
+        if let Some(chain) = field(output, "chain") {
+            self.chain = Some(chain);
+        }
+        self.account = field(output, "from");
+        self.signed_tx = field(output, "signed_tx");
+        self.tx_hash = field(output, "tx_hash");
+        self.status = field(output, "status");
+        if self.status == Some(TxStatus::DryRun) {
+            self.decision = Decision::DryRun;
+        }
+        self
+    }
+
+    pub fn rejected(mut self, error: String) -> Self {
+        self.decision = Decision::Rejected;
+        self.error = Some(error);
+        self
+    }
+
+    pub fn failed(mut self, error: String) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    /// Stamp the finish time, append the record to the audit log and pass it to
+    /// the listener of the surrounding [`scope_with_events`], if any.
+    pub fn finish(mut self) {
+        self.finished_at = Utc::now();
+        if let Err(e) = append(audit_log_path(), &self) {
+            tracing::error!("failed to write audit log: {:#}", e);
+        }
+        if let Ok(Some(events)) = CONTEXT.try_with(|c| c.events.clone()) {
+            let _ = events.send(self);
+        }
+    }
+}
+
+fn field<T: DeserializeOwned>(output: &Value, name: &str) -> Option<T> {
+    output
+        .get(name)
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+}
+
+#[derive(Debug, Clone)]
+struct AuditContext {
+    prompt: String,
+    model: String,
+    events: Option<UnboundedSender<AuditRecord>>,
+}
+
+tokio::task_local! {
+    static CONTEXT: AuditContext;
+}
+
+/// Run an agent prompt so tool calls made while handling it are audited with
+/// the prompt and the model that produced them.
+pub async fn scope<F: Future>(model: &str, prompt: &str, f: F) -> F::Output {
+    let context = AuditContext {
+        prompt: prompt.to_string(),
+        model: model.to_string(),
+        events: None,
+    };
+    CONTEXT.scope(context, f).await
+}
+
+/// Like [`scope`], and every record is also sent to `events` as soon as the call finishes.
+pub async fn scope_with_events<F: Future>(
+    model: &str,
+    prompt: &str,
+    events: UnboundedSender<AuditRecord>,
+    f: F,
+) -> F::Output {
+    let context = AuditContext {
+        prompt: prompt.to_string(),
+        model: model.to_string(),
+        events: Some(events),
+    };
+    CONTEXT.scope(context, f).await
+}
+
+// Serializes appends so concurrent tool calls never interleave lines.
+static WRITE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+pub fn append(path: &Path, record: &AuditRecord) -> Result<()> {
+    let _guard = WRITE_LOCK.lock().unwrap();
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("open audit log {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub chain: Option<String>,
+    pub account: Option<Address>,
+    pub tool: Option<String>,
+    pub user: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        let chain = match &self.chain {
+            Some(chain) => record
+                .chain
+                .as_ref()
+                .is_some_and(|c| c.eq_ignore_ascii_case(chain)),
+            None => true,
+        };
+        chain
+            && self.account.is_none_or(|a| record.account == Some(a))
+            && self.tool.as_ref().is_none_or(|t| &record.tool == t)
+            && self
+                .user
+                .as_ref()
+                .is_none_or(|u| record.user.as_ref() == Some(u))
+            && self.since.is_none_or(|since| record.started_at >= since)
+            && self.until.is_none_or(|until| record.started_at < until)
+    }
+}
+
+/// Records in the log matching `filter`, oldest first. A missing log has no records.
+pub fn query(path: &Path, filter: &AuditFilter) -> Result<Vec<AuditRecord>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e).with_context(|| format!("open audit log {}", path.display())),
+    };
+    let mut records = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: AuditRecord = serde_json::from_str(&line)
+            .with_context(|| format!("parse {} line {}", path.display(), i + 1))?;
+        if filter.matches(&record) {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+#[tokio::test]
+async fn test_audit_log_query() {
+    use serde_json::json;
+    use std::str::FromStr;
+
+    let path = std::env::temp_dir().join(format!("rig-eth-audit-{}.jsonl", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let account = Address::from_str("0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266").unwrap();
+
+    let transfer = scope("mock", "send 1 ETH on base", async {
+        AuditRecord::new("eth_transfer", json!({"chain": "8453", "amount": "1"})).with_output(
+            &json!({"chain": "base", "from": account, "status": "pending", "tx_hash": B256::ZERO}),
+        )
+    })
+    .await;
+    assert_eq!(transfer.prompt.as_deref(), Some("send 1 ETH on base"));
+    assert_eq!(transfer.chain.as_deref(), Some("base"));
+    append(&path, &transfer).unwrap();
+    let rejected = AuditRecord::new("erc20_transfer", json!({"chain": "arb"}))
+        .rejected("invalid arguments".to_string());
+    assert_eq!(rejected.model, None);
+    append(&path, &rejected).unwrap();
+
+    let all = query(&path, &AuditFilter::default()).unwrap();
+    assert_eq!(all.len(), 2);
+    let by_chain = AuditFilter {
+        chain: Some("Base".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(query(&path, &by_chain).unwrap()[0].tool, "eth_transfer");
+    let by_account = AuditFilter {
+        account: Some(account),
+        tool: Some("erc20_transfer".to_string()),
+        ..Default::default()
+    };
+    assert!(query(&path, &by_account).unwrap().is_empty());
+    let future = AuditFilter {
+        since: Some(Utc::now() + chrono::Duration::days(1)),
+        ..Default::default()
+    };
+    assert!(query(&path, &future).unwrap().is_empty());
+    fs::remove_file(&path).unwrap();
+}
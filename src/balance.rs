@@ -0,0 +1,122 @@
+use alloy::{
+    primitives::{
+        utils::{format_ether, format_units},
+        Address,
+    },
+    providers::{Provider, ProviderBuilder, RootProvider},
+    transports::http::{Client, Http},
+};
+use rig::{completion::ToolDefinition, tool::Tool};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::{
+    chains::get_chain_info,
+    erc20_transfer::IERC20,
+    telemetry::rpc,
+    tool_schema::{address_schema, chain_schema, parameters},
+    wallet::local_address,
+};
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BalanceArgs {
+    /// The chain name, alias or chain ID, such as arbitrum
+    #[schemars(schema_with = "chain_schema")]
+    chain: String,
+    /// The address to check. Omit to check the agent's own account
+    #[serde(default)]
+    #[schemars(schema_with = "address_schema")]
+    address: Option<String>,
+    /// The address of the ERC20 token contract. Omit for the ETH balance
+    #[serde(default)]
+    #[schemars(schema_with = "address_schema")]
+    token_address: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Balance error: {message}")]
+pub struct BalanceError {
+    message: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetBalance;
+impl Tool for GetBalance {
+    const NAME: &'static str = "get_balance";
+
+    type Error = BalanceError;
+    type Args = BalanceArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "get_balance".to_string(),
+            description: "Get the ETH or ERC20 token balance of an address".to_string(),
+            parameters: parameters::<BalanceArgs>(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let chain_info = get_chain_info(&args.chain).map_err(|e| BalanceError {
+            message: e.to_string(),
+        })?;
+        let parse = |field: &str, value: &str| {
+            Address::from_str(value).map_err(|e| BalanceError {
+                message: format!("invalid {}: {}", field, e),
+            })
+        };
+        let owner = match &args.address {
+            Some(address) => parse("address", address)?,
+            None => local_address(),
+        };
+        let token = match &args.token_address {
+            Some(token) => Some(parse("token_address", token)?),
+            None => None,
+        };
+
+        let balance = get_balance(
+            owner,
+            token,
+            chain_info.chain.clone(),
+            chain_info.provider_url,
+        )
+        .await
+        .map_err(|e| BalanceError {
+            message: format!("get_balance error: {}", e),
+        })?;
+        Ok(format!(
+            "{} holds {} {} on {}",
+            owner,
+            balance,
+            token.map_or("ETH".to_string(), |t| format!("of token {}", t)),
+            chain_info.chain
+        ))
+    }
+}
+
+// Balance formatted with the token's decimals, or in ETH when no token is given.
+async fn get_balance(
+    owner: Address,
+    token: Option<Address>,
+    chain: String,
+    provider_url: String,
+) -> std::result::Result<String, anyhow::Error> {
+    let provider: RootProvider<Http<Client>> =
+        ProviderBuilder::new().on_http(provider_url.parse()?);
+
+    match token {
+        Some(token) => {
+            let erc20 = IERC20::IERC20Instance::new(token, provider);
+            let balance = rpc(&chain, "balanceOf", erc20.balanceOf(owner).call())
+                .await?
+                ._0;
+            let decimals = rpc(&chain, "decimals", erc20.decimals().call()).await?._0;
+            Ok(format_units(balance, decimals)?)
+        }
+        None => Ok(format_ether(
+            rpc(&chain, "getBalance", provider.get_balance(owner)).await?,
+        )),
+    }
+}
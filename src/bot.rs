@@ -0,0 +1,478 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use rig::completion::{Chat, Message};
+use serde::Deserialize;
+use serde_json::{json, Value};
+#[cfg(test)]
+use tokio::sync::mpsc;
+use tracing::{info_span, warn, Instrument};
+
+use crate::{
+    audit,
+    proposal::{self, ProposalStore},
+    tx_output::{render, TxOutput},
+};
+
+const HELP: &str = "Ask me to check balances, quote swaps or send transactions. \
+Transactions wait for you to approve them. /clear forgets the conversation.";
+
+/// Seconds a Telegram `getUpdates` call waits for new updates.
+const POLL_TIMEOUT_SECS: u64 = 30;
+
+/// Wait after a failed receive, doubled on every failure in a row up to the max.
+const RECEIVE_RETRY_MIN: Duration = Duration::from_secs(1);
+const RECEIVE_RETRY_MAX: Duration = Duration::from_secs(60);
+
+/// What a user did in a chat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incoming {
+    Message {
+        chat: String,
+        text: String,
+    },
+    /// An inline button was pressed; `data` is the [`Button`]'s.
+    Button {
+        chat: String,
+        data: String,
+    },
+}
+
+/// Inline button under a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Button {
+    pub label: String,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outgoing {
+    pub chat: String,
+    pub text: String,
+    pub buttons: Vec<Button>,
+}
+
+impl Outgoing {
+    fn text(chat: &str, text: impl Into<String>) -> Self {
+        Outgoing {
+            chat: chat.to_string(),
+            text: text.into(),
+            buttons: vec![],
+        }
+    }
+}
+
+/// A chat service the bot talks through.
+pub trait Transport {
+    /// Wait for the next batch of updates, `None` once the transport is closed.
+    fn receive(&mut self) -> impl Future<Output = Result<Option<Vec<Incoming>>>> + Send;
+    fn send(&self, message: Outgoing) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Routes chat messages into the agent, one conversation per chat, and turns
+/// proposed transactions into approve/reject buttons. Transaction tools should
+/// run with approval required, so nothing is sent without a button press.
+pub struct Bot<A, T> {
+    agent: A,
+    model: String,
+    transport: T,
+    /// Chats allowed to use the bot, everyone else is turned away.
+    allowed_chats: HashSet<String>,
+    histories: HashMap<String, Vec<Message>>,
+    proposals: ProposalStore,
+}
+
+impl<A: Chat, T: Transport> Bot<A, T> {
+    pub fn new(agent: A, model: &str, transport: T, allowed_chats: HashSet<String>) -> Self {
+        Bot {
+            agent,
+            model: model.to_string(),
+            transport,
+            allowed_chats,
+            histories: HashMap::new(),
+            proposals: ProposalStore::default(),
+        }
+    }
+
+    /// Handle updates until the transport closes. Failed receives, e.g. network
+    /// errors or a 5xx from the API, are retried with backoff.
+    pub async fn run(&mut self) -> Result<()> {
+        let mut retry = RECEIVE_RETRY_MIN;
+        loop {
+            let updates = match self.transport.receive().await {
+                Ok(Some(updates)) => updates,
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    warn!(retry_in = ?retry, "failed to receive bot updates: {:#}", e);
+                    tokio::time::sleep(retry).await;
+                    retry = (retry * 2).min(RECEIVE_RETRY_MAX);
+                    continue;
+                }
+            };
+            retry = RECEIVE_RETRY_MIN;
+            for update in updates {
+                let replies = self.handle(update).await;
+                for reply in replies {
+                    if let Err(e) = self.transport.send(reply).await {
+                        warn!("failed to send bot message: {:#}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle(&mut self, update: Incoming) -> Vec<Outgoing> {
+        let chat = match &update {
+            Incoming::Message { chat, .. } | Incoming::Button { chat, .. } => chat.clone(),
+        };
+        if !self.allowed_chats.contains(&chat) {
+            warn!(chat, "message from a chat that is not allowed");
+            return vec![Outgoing::text(
+                &chat,
+                "This chat is not allowed to use the bot.",
+            )];
+        }
+        let reply = match update {
+            Incoming::Message { text, .. } => match text.trim() {
+                "/start" | "/help" => Outgoing::text(&chat, HELP),
+                "/clear" => {
+                    self.histories.remove(&chat);
+                    Outgoing::text(&chat, "Conversation cleared.")
+                }
+                text => self.prompt(&chat, text).await,
+            },
+            Incoming::Button { data, .. } => self.press(&chat, &data).await,
+        };
+        vec![reply]
+    }
+
+    async fn prompt(&mut self, chat: &str, text: &str) -> Outgoing {
+        let history = self.histories.entry(chat.to_string()).or_default();
+        let response = audit::scope(
+            &self.model,
+            text,
+            proposal::in_chat(chat, self.agent.chat(text, history.clone())),
+        )
+        .instrument(info_span!("agent_prompt", model = self.model))
+        .await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => return Outgoing::text(chat, format!("error: {}", e)),
+        };
+        history.push(Message {
+            role: "user".to_string(),
+            content: text.to_string(),
+        });
+        history.push(Message {
+            role: "assistant".to_string(),
+            content: response.clone(),
+        });
+
+        let mut reply = Outgoing::text(chat, render(&response));
+        let proposal_id = serde_json::from_str::<TxOutput>(&response)
+            .ok()
+            .and_then(|output| output.proposal_id);
+        if let Some(id) = proposal_id {
+            reply.buttons = vec![
+                Button {
+                    label: "Approve".to_string(),
+                    data: format!("approve:{}", id),
+                },
+                Button {
+                    label: "Reject".to_string(),
+                    data: format!("reject:{}", id),
+                },
+            ];
+        }
+        reply
+    }
+
+    // Within the chat, so a button can only act on the chat's own proposals.
+    async fn press(&self, chat: &str, data: &str) -> Outgoing {
+        let text = proposal::in_chat(chat, async {
+            match data.split_once(':') {
+                Some(("approve", id)) => match self.proposals.approve(id).await {
+                    Ok(output) => output.to_string(),
+                    Err(e) => format!("error: {:#}", e),
+                },
+                Some(("reject", id)) => match self.proposals.reject(id) {
+                    Ok(proposal) => format!("Rejected: {}", proposal.output.summary),
+                    Err(e) => format!("error: {:#}", e),
+                },
+                _ => format!("unknown button '{}'", data),
+            }
+        })
+        .await;
+        Outgoing::text(chat, text)
+    }
+}
+
+/// The Telegram Bot API, or any server speaking it, polled with `getUpdates`.
+pub struct TelegramTransport {
+    client: reqwest::Client,
+    /// `<api_url>/bot<token>`
+    base_url: String,
+    offset: i64,
+}
+
+#[derive(Deserialize)]
+struct TelegramResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Update {
+    update_id: i64,
+    message: Option<TelegramMessage>,
+    callback_query: Option<CallbackQuery>,
+}
+
+#[derive(Deserialize)]
+struct TelegramMessage {
+    chat: TelegramChat,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TelegramChat {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct CallbackQuery {
+    id: String,
+    message: Option<TelegramMessage>,
+    data: Option<String>,
+}
+
+impl TelegramTransport {
+    pub fn new(api_url: &str, token: &str) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(POLL_TIMEOUT_SECS + 10))
+            .build()?;
+        Ok(TelegramTransport {
+            client,
+            base_url: format!("{}/bot{}", api_url.trim_end_matches('/'), token),
+            offset: 0,
+        })
+    }
+
+    async fn call<T: serde::de::DeserializeOwned>(&self, method: &str, body: Value) -> Result<T> {
+        let response: TelegramResponse<T> = self
+            .client
+            .post(format!("{}/{}", self.base_url, method))
+            .json(&body)
+            .send()
+            .await
+            // The URL holds the token, keep it out of errors.
+            .map_err(|e| anyhow!("telegram {}: {}", method, e.without_url()))?
+            .json()
+            .await
+            .with_context(|| format!("telegram {}: parse response", method))?;
+        match response.result {
+            Some(result) if response.ok => Ok(result),
+            _ => Err(anyhow!(
+                "telegram {}: {}",
+                method,
+                response.description.unwrap_or_default()
+            )),
+        }
+    }
+}
+
+impl Transport for TelegramTransport {
+    async fn receive(&mut self) -> Result<Option<Vec<Incoming>>> {
+        let updates: Vec<Update> = self
+            .call(
+                "getUpdates",
+                json!({
+                    "offset": self.offset,
+                    "timeout": POLL_TIMEOUT_SECS,
+                    "allowed_updates": ["message", "callback_query"],
+                }),
+            )
+            .await?;
+        let mut incoming = vec![];
+        for update in updates {
+            self.offset = self.offset.max(update.update_id + 1);
+            if let Some(TelegramMessage {
+                chat,
+                text: Some(text),
+            }) = update.message
+            {
+                incoming.push(Incoming::Message {
+                    chat: chat.id.to_string(),
+                    text,
+                });
+            }
+            if let Some(query) = update.callback_query {
+                // Stops the client's loading indicator on the button.
+                let answered: Result<bool> = self
+                    .call(
+                        "answerCallbackQuery",
+                        json!({ "callback_query_id": query.id }),
+                    )
+                    .await;
+                if let Err(e) = answered {
+                    warn!("{:#}", e);
+                }
+                if let (Some(message), Some(data)) = (query.message, query.data) {
+                    incoming.push(Incoming::Button {
+                        chat: message.chat.id.to_string(),
+                        data,
+                    });
+                }
+            }
+        }
+        Ok(Some(incoming))
+    }
+
+    async fn send(&self, message: Outgoing) -> Result<()> {
+        let mut body = json!({ "chat_id": message.chat, "text": message.text });
+        if !message.buttons.is_empty() {
+            let buttons: Vec<Value> = message
+                .buttons
+                .iter()
+                .map(|b| json!({ "text": b.label, "callback_data": b.data }))
+                .collect();
+            body["reply_markup"] = json!({ "inline_keyboard": [buttons] });
+        }
+        let _: Value = self.call("sendMessage", body).await?;
+        Ok(())
+    }
+}
+
+/// In-memory transport for tests: updates come from a channel and replies go to another.
+#[cfg(test)]
+pub struct StubTransport {
+    incoming: mpsc::UnboundedReceiver<Incoming>,
+    outgoing: mpsc::UnboundedSender<Outgoing>,
+    /// Receives that fail before updates come through, like a flaky network.
+    pub failures: usize,
+}
+
+#[cfg(test)]
+impl StubTransport {
+    /// The transport, a sender for updates (drop it to close the transport) and
+    /// a receiver for the bot's replies.
+    pub fn new() -> (
+        Self,
+        mpsc::UnboundedSender<Incoming>,
+        mpsc::UnboundedReceiver<Outgoing>,
+    ) {
+        let (incoming_tx, incoming) = mpsc::unbounded_channel();
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+        (
+            StubTransport {
+                incoming,
+                outgoing,
+                failures: 0,
+            },
+            incoming_tx,
+            outgoing_rx,
+        )
+    }
+}
+
+#[cfg(test)]
+impl Transport for StubTransport {
+    async fn receive(&mut self) -> Result<Option<Vec<Incoming>>> {
+        if self.failures > 0 {
+            self.failures -= 1;
+            return Err(anyhow!("stub transport unavailable"));
+        }
+        Ok(self.incoming.recv().await.map(|update| vec![update]))
+    }
+
+    async fn send(&self, message: Outgoing) -> Result<()> {
+        self.outgoing
+            .send(message)
+            .map_err(|_| anyhow!("stub transport closed"))
+    }
+}
+
+#[tokio::test]
+async fn test_bot_stub() {
+    use crate::{
+        agent::EthAgent,
+        chains::get_chain_info,
+        llm::AgentSpec,
+        mock_llm::{MockModel, MockStep},
+    };
+    use alloy::{network::TransactionBuilder, primitives::Address, rpc::types::TransactionRequest};
+    use rig::agent::AgentBuilder;
+
+    // A proposal as the transfer tools leave it with approval required.
+    let to = Address::repeat_byte(0x22);
+    let output = TxOutput::new(
+        "eth_transfer",
+        format!("transfer 1 ETH to {}", to),
+        &get_chain_info("base").unwrap(),
+        to,
+        None,
+        "1".to_string(),
+    );
+    let proposal = proposal::in_chat("1", async {
+        ProposalStore::default().propose(
+            output,
+            TransactionRequest::default().with_to(to),
+            crate::fee::FeeTier::Normal,
+            Duration::from_secs(600),
+        )
+    })
+    .await
+    .unwrap();
+    assert_eq!(proposal.chat.as_deref(), Some("1"));
+    let model = MockModel::new(vec![MockStep::message(
+        &serde_json::to_string(&proposal.output).unwrap(),
+    )]);
+
+    let (mut transport, updates, mut replies) = StubTransport::new();
+    // The bot keeps polling through a failed receive.
+    transport.failures = 1;
+    let allowed = HashSet::from(["1".to_string(), "3".to_string()]);
+    let mut bot = Bot::new(
+        EthAgent.build(AgentBuilder::new(model.clone())),
+        "mock",
+        transport,
+        allowed,
+    );
+    let message = |chat: &str, text: &str| Incoming::Message {
+        chat: chat.to_string(),
+        text: text.to_string(),
+    };
+    updates.send(message("2", "send 1 ETH")).unwrap();
+    updates.send(message("1", "send 1 ETH")).unwrap();
+    let press = |chat: &str, data: String| Incoming::Button {
+        chat: chat.to_string(),
+        data,
+    };
+    // Another allowed chat can't act on chat 1's proposal.
+    updates
+        .send(press("3", format!("approve:{}", proposal.id)))
+        .unwrap();
+    updates
+        .send(press("1", format!("reject:{}", proposal.id)))
+        .unwrap();
+    drop(updates);
+    bot.run().await.unwrap();
+
+    assert!(replies.recv().await.unwrap().text.contains("not allowed"));
+    let proposed = replies.recv().await.unwrap();
+    assert!(proposed.text.contains("proposed, awaiting approval"));
+    assert_eq!(proposed.buttons.len(), 2);
+    assert_eq!(proposed.buttons[1].data, format!("reject:{}", proposal.id));
+    let other_chat = replies.recv().await.unwrap();
+    assert_eq!(other_chat.chat, "3");
+    assert!(other_chat.text.contains("no proposal"));
+    let rejected = replies.recv().await.unwrap();
+    assert!(rejected.text.starts_with("Rejected: transfer 1 ETH"));
+    assert_eq!(model.prompts().len(), 1);
+    assert_eq!(bot.histories["1"].len(), 2);
+}
@@ -1,25 +1,238 @@
-use std::{collections::HashMap, fs};
+use std::{collections::HashMap, fs, path::Path, sync::RwLock};
 
+use alloy::primitives::{utils::parse_units, B256};
+use anyhow::{anyhow, Result};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    settings::config_path,
+    token_list::{import_token_lists, TokenListEntry},
+};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ChainInfo {
     pub chain: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub chain_id: u64,
     #[serde(skip_serializing)]
     pub provider_url: String,
     pub tokens: HashMap<String, String>, // token_symbol => token_address
+    #[serde(default, skip_serializing)]
+    pub token_metadata: HashMap<String, TokenListEntry>, // token_symbol => imported token-list entry
     pub swap_router: String,
+    #[serde(default)]
+    pub explorer_url: Option<String>, // block explorer base URL, e.g. https://etherscan.io
+    #[serde(default, skip_serializing)]
+    pub max_fee_gwei: Option<String>, // most any transaction pays per gas, e.g. "50"
+    #[serde(default, skip_serializing)]
+    pub stablecoin: Option<String>, // symbol in `tokens` fees are priced in, e.g. USDC
+}
+
+pub static CHAIN_INFOS: Lazy<Vec<ChainInfo>> =
+    Lazy::new(|| load_chain_infos(config_path()).expect("Failed to load chains.json"));
+
+// Chains of the configs added with `add_chain_config`, looked up after the main config's.
+static ADDED_CHAINS: Lazy<RwLock<Vec<ChainInfo>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Read a chain config and the token lists in the `tokenlists` directory next to it.
+pub fn load_chain_infos(path: &Path) -> Result<Vec<ChainInfo>> {
+    let content = fs::read_to_string(path)?;
+    let mut chains: Vec<ChainInfo> = serde_json::from_str(&content)?;
+
+    // Tokens from local token lists are merged in, hand-maintained entries win on conflict.
+    let token_lists_dir = path.parent().unwrap_or(Path::new(".")).join("tokenlists");
+    let report = import_token_lists(&mut chains, &token_lists_dir)?;
+    for c in &report.conflicts {
+        tracing::warn!(
+            "token list '{}' conflict on {}: {} is already {}, ignoring {}",
+            c.list,
+            c.chain,
+            c.symbol,
+            c.existing_address,
+            c.imported_address
+        );
+    }
+    Ok(chains)
+}
+
+/// Make the chains of another config available, e.g. one written for a local node.
+/// Chains of the main config take precedence.
+pub fn add_chain_config(path: &Path) -> Result<()> {
+    let chains = load_chain_infos(path)
+        .map_err(|e| anyhow!("load chain config {}: {}", path.display(), e))?;
+    ADDED_CHAINS.write().unwrap().extend(chains);
+    Ok(())
 }
 
-pub static CHAIN_INFOS: Lazy<Vec<ChainInfo>> = Lazy::new(|| {
-    let content = fs::read_to_string("configs/chains.json").expect("Failed to read chains.json");
-    serde_json::from_str(&content).expect("Failed to parse JSON")
-});
+/// Chains of the main config, then of the added ones.
+pub fn chain_infos() -> Vec<ChainInfo> {
+    let added = ADDED_CHAINS.read().unwrap();
+    CHAIN_INFOS.iter().chain(added.iter()).cloned().collect()
+}
+
+impl ChainInfo {
+    /// The configured fee cap in wei per gas.
+    pub fn max_fee_per_gas(&self) -> Result<Option<u128>> {
+        let Some(gwei) = &self.max_fee_gwei else {
+            return Ok(None);
+        };
+        let invalid = |e: &dyn std::fmt::Display| {
+            anyhow!("{}: invalid max_fee_gwei '{}': {}", self.chain, gwei, e)
+        };
+        let wei = parse_units(gwei, "gwei").map_err(|e| invalid(&e))?;
+        u128::try_from(wei.get_absolute())
+            .map(Some)
+            .map_err(|e| invalid(&e))
+    }
+
+    /// Block explorer page for a transaction, when the chain has an explorer configured.
+    pub fn tx_url(&self, hash: B256) -> Option<String> {
+        self.explorer_url
+            .as_ref()
+            .map(|url| format!("{}/tx/{}", url.trim_end_matches('/'), hash))
+    }
+}
 
-pub fn get_chain_info(chain_name: &str) -> Option<ChainInfo> {
-    CHAIN_INFOS
+/// Compact agent context: one line per chain, token addresses are fetched with `list_tokens`.
+pub fn chains_context() -> String {
+    let chains: Vec<String> = chain_infos()
         .iter()
-        .find(|info| info.chain == chain_name)
-        .cloned()
+        .map(|info| format!("{} (chain_id {})", info.chain, info.chain_id))
+        .collect();
+    format!(
+        "Configured chains: {}. Use the list_chains tool for aliases and the list_tokens tool \
+         to look up token addresses instead of guessing them.",
+        chains.join(", ")
+    )
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unknown chain '{query}'{}", did_you_mean(.suggestions))]
+pub struct ChainNotFound {
+    pub query: String,
+    pub suggestions: Vec<String>,
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!(", did you mean: {}?", suggestions.join(", "))
+    }
+}
+
+pub fn get_chain_info(chain_name: &str) -> Result<ChainInfo, ChainNotFound> {
+    match resolve_chain(&CHAIN_INFOS, chain_name) {
+        Ok(info) => Ok(info.clone()),
+        Err(e) => resolve_chain(&ADDED_CHAINS.read().unwrap(), chain_name)
+            .cloned()
+            .map_err(|_| e),
+    }
+}
+
+/// Every name, alias and chain ID a chain can be referred to by, main config first.
+pub fn chain_names() -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for info in chain_infos() {
+        let all = std::iter::once(info.chain.clone())
+            .chain(info.aliases.iter().cloned())
+            .chain(std::iter::once(info.chain_id.to_string()));
+        for name in all {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Resolve a chain by name, configured alias or numeric chain ID, ignoring case.
+pub fn resolve_chain<'a>(
+    chains: &'a [ChainInfo],
+    query: &str,
+) -> Result<&'a ChainInfo, ChainNotFound> {
+    let needle = query.trim().to_lowercase();
+    let found = match needle.parse::<u64>() {
+        Ok(chain_id) => chains.iter().find(|info| info.chain_id == chain_id),
+        Err(_) => chains.iter().find(|info| {
+            info.chain.to_lowercase() == needle
+                || info.aliases.iter().any(|a| a.to_lowercase() == needle)
+        }),
+    };
+    found.ok_or_else(|| ChainNotFound {
+        query: query.to_string(),
+        suggestions: suggest_chains(chains, &needle),
+    })
+}
+
+// Chains whose name or an alias is close to the query, best match first.
+fn suggest_chains(chains: &[ChainInfo], needle: &str) -> Vec<String> {
+    let mut scored: Vec<(usize, &str)> = chains
+        .iter()
+        .filter_map(|info| {
+            std::iter::once(&info.chain)
+                .chain(&info.aliases)
+                .map(|name| {
+                    let name = name.to_lowercase();
+                    if !needle.is_empty() && (name.starts_with(needle) || needle.starts_with(&name))
+                    {
+                        0
+                    } else {
+                        edit_distance(&name, needle)
+                    }
+                })
+                .min()
+                .filter(|d| *d <= 3)
+                .map(|d| (d, info.chain.as_str()))
+        })
+        .collect();
+    scored.sort();
+    scored
+        .into_iter()
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+#[test]
+fn test_resolve_chain() {
+    let chains: Vec<ChainInfo> = serde_json::from_str(
+        r#"[
+            {"chain": "base", "aliases": ["base mainnet"], "chain_id": 8453, "provider_url": "", "tokens": {}, "swap_router": ""},
+            {"chain": "arbitrum", "aliases": ["arb", "arbitrum one"], "chain_id": 42161, "provider_url": "", "tokens": {}, "swap_router": ""}
+        ]"#,
+    )
+    .unwrap();
+
+    for query in ["base", "Base", " base mainnet ", "8453"] {
+        assert_eq!(resolve_chain(&chains, query).unwrap().chain, "base");
+    }
+    assert_eq!(resolve_chain(&chains, "ARB").unwrap().chain, "arbitrum");
+
+    let err = resolve_chain(&chains, "arbitrun").unwrap_err();
+    assert_eq!(err.suggestions, vec!["arbitrum"]);
+    assert_eq!(
+        err.to_string(),
+        "unknown chain 'arbitrun', did you mean: arbitrum?"
+    );
+    assert!(resolve_chain(&chains, "solana")
+        .unwrap_err()
+        .suggestions
+        .is_empty());
+    assert!(resolve_chain(&chains, "1").is_err());
 }
@@ -0,0 +1,375 @@
+use std::{net::SocketAddr, path::PathBuf, time::Duration as StdDuration};
+
+use alloy::primitives::Address;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use clap::{Parser, Subcommand};
+use rig::completion::{Chat, Prompt};
+use tracing::{info_span, Instrument};
+
+use crate::{
+    agent::{toolset, EthAgent, TOOL_NAMES},
+    audit::{self, AuditFilter},
+    bot::{Bot, TelegramTransport},
+    chains::add_chain_config,
+    llm::{LlmConfig, LlmConfigs},
+    mcp::McpServer,
+    metrics,
+    proposal::{ProposalStatus, ProposalStore},
+    repl,
+    router::Router,
+    server,
+    settings::{
+        set_approval_required, set_audit_log_path, set_config_path, set_dry_run, set_nonces_path,
+        set_proposal_ttl, set_proposals_path, set_stuck_after, DEFAULT_PROPOSAL_TTL_SECS,
+        DEFAULT_STUCK_AFTER_SECS,
+    },
+    telemetry::LogFormat,
+    tx_output::render,
+    tx_tracker::TX_TRACKER,
+    users::Users,
+};
+
+/// How often sent transactions are checked for being stuck in the long-running modes:
+/// repl, serve and bot. One-shot commands exit before any could get stuck.
+const STUCK_CHECK_INTERVAL: StdDuration = StdDuration::from_secs(30);
+
+#[derive(Parser)]
+#[command(about = "Interact with EVM chains through an AI agent")]
+pub struct Cli {
+    /// Path to the chain config, token lists are read from `tokenlists/` next to it
+    #[arg(long, global = true, default_value = "configs/chains.json")]
+    pub config: PathBuf,
+
+    /// More chain configs, e.g. for a local node, looked up after the main one
+    #[arg(long, global = true)]
+    pub extra_chains: Vec<PathBuf>,
+
+    /// Provider profiles for the LLM
+    #[arg(long, global = true, default_value = "configs/llm.json")]
+    pub llm_config: PathBuf,
+
+    /// Provider profile to use, defaults to the one marked `default` in the LLM config
+    #[arg(long, global = true, env = "RIG_ETH_LLM")]
+    pub llm: Option<String>,
+
+    /// Override the model of the selected profile
+    #[arg(long, global = true, env = "RIG_ETH_MODEL")]
+    pub model: Option<String>,
+
+    /// Override the base URL of the selected profile
+    #[arg(long, global = true, env = "RIG_ETH_PROVIDER_URL")]
+    pub provider_url: Option<String>,
+
+    /// Classify each prompt and hand it to a specialized sub-agent instead of one agent with every tool
+    #[arg(long, global = true)]
+    pub router: bool,
+
+    /// Validate tool calls and estimate their cost without signing or sending transactions
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// JSON Lines file every tool invocation is appended to
+    #[arg(long, global = true, default_value = "logs/audit.jsonl")]
+    pub audit_log: PathBuf,
+
+    /// Store transactions as proposals to approve or reject, instead of sending them right away
+    #[arg(long, global = true, env = "RIG_ETH_REQUIRE_APPROVAL")]
+    pub require_approval: bool,
+
+    /// Seconds a proposal can be approved for
+    #[arg(long, global = true, env = "RIG_ETH_PROPOSAL_TTL", default_value_t = DEFAULT_PROPOSAL_TTL_SECS)]
+    pub proposal_ttl: u64,
+
+    /// JSON file proposals are kept in
+    #[arg(long, global = true, default_value = "data/proposals.json")]
+    pub proposals: PathBuf,
+
+    /// JSON file nonces of transactions in flight are kept in. Sent transactions
+    /// are tracked in sent_txs.json next to it
+    #[arg(long, global = true, default_value = "data/nonces.json")]
+    pub nonces: PathBuf,
+
+    /// Seconds a sent transaction can stay pending before it is reported as stuck
+    #[arg(long, global = true, env = "RIG_ETH_STUCK_AFTER", default_value_t = DEFAULT_STUCK_AFTER_SECS)]
+    pub stuck_after: u64,
+
+    /// Format of the logs written to stderr, filtered with `RUST_LOG`
+    #[arg(long, global = true, env = "RIG_ETH_LOG_FORMAT", value_enum, default_value_t = LogFormat::Pretty)]
+    pub log_format: LogFormat,
+
+    /// Export spans to an OpenTelemetry collector over OTLP/gRPC, e.g. http://localhost:4317
+    #[arg(long, global = true, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+
+    /// Serve Prometheus metrics on `http://<addr>/metrics` while running, e.g. 127.0.0.1:9464
+    #[arg(long, global = true, env = "RIG_ETH_METRICS_ADDR")]
+    pub metrics_addr: Option<SocketAddr>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Send a single prompt to the agent and print the response
+    Prompt { text: String },
+    /// Start an interactive chat session that keeps the conversation history
+    Repl,
+    /// List, approve or reject proposed transactions
+    Proposals {
+        #[command(subcommand)]
+        action: ProposalCommand,
+    },
+    /// Serve the agent and the tools over HTTP
+    Serve {
+        #[arg(long, env = "RIG_ETH_LISTEN", default_value = "127.0.0.1:8080")]
+        addr: SocketAddr,
+        /// Users allowed to call the API, each with a token, e.g. configs/users.json.
+        /// Without it the API is open, and only listens on loopback addresses.
+        #[arg(long, env = "RIG_ETH_USERS")]
+        users: Option<PathBuf>,
+    },
+    /// Chat with the agent through a Telegram bot; transactions always wait for approval
+    Bot {
+        /// Bot token from BotFather
+        #[arg(long, env = "TELEGRAM_BOT_TOKEN", hide_env_values = true)]
+        token: String,
+        /// Base URL of the Bot API, for self-hosted or compatible servers
+        #[arg(
+            long,
+            env = "TELEGRAM_API_URL",
+            default_value = "https://api.telegram.org"
+        )]
+        api_url: String,
+        /// Chat IDs allowed to use the bot, comma separated
+        #[arg(
+            long = "allow-chat",
+            env = "RIG_ETH_BOT_CHATS",
+            value_delimiter = ',',
+            required = true
+        )]
+        allowed_chats: Vec<String>,
+    },
+    /// Invoke a tool directly with JSON arguments, without an LLM
+    Tool {
+        name: String,
+        #[arg(long, default_value = "{}")]
+        json: String,
+    },
+    /// Serve the tools to other agent hosts over MCP on stdin/stdout, without an LLM
+    Mcp,
+    /// Print audit log records, one JSON object per line
+    Audit {
+        /// Chain the call was made on
+        #[arg(long)]
+        chain: Option<String>,
+        /// Account that signed the transaction
+        #[arg(long)]
+        account: Option<Address>,
+        /// Tool name
+        #[arg(long)]
+        tool: Option<String>,
+        /// User of the HTTP API
+        #[arg(long)]
+        user: Option<String>,
+        /// Only records from this date (YYYY-MM-DD) or RFC 3339 time on
+        #[arg(long, value_parser = parse_since)]
+        since: Option<DateTime<Utc>>,
+        /// Only records up to and including this date, or before this RFC 3339 time
+        #[arg(long, value_parser = parse_until)]
+        until: Option<DateTime<Utc>>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ProposalCommand {
+    /// Print proposals, one JSON object per line
+    List {
+        #[arg(long, value_enum)]
+        status: Option<ProposalStatus>,
+    },
+    /// Sign and send a pending proposal as the user who proposed it
+    Approve {
+        id: String,
+        /// Users file to look up the proposer in, for proposals made through the HTTP API
+        #[arg(long, env = "RIG_ETH_USERS")]
+        users: Option<PathBuf>,
+    },
+    /// Discard a pending proposal
+    Reject { id: String },
+}
+
+pub async fn run(cli: Cli) -> Result<()> {
+    set_config_path(cli.config.clone());
+    for path in &cli.extra_chains {
+        add_chain_config(path)?;
+    }
+    set_dry_run(cli.dry_run);
+    set_audit_log_path(cli.audit_log.clone());
+    set_proposals_path(cli.proposals.clone());
+    set_approval_required(cli.require_approval);
+    set_proposal_ttl(StdDuration::from_secs(cli.proposal_ttl));
+    set_nonces_path(cli.nonces.clone());
+    set_stuck_after(StdDuration::from_secs(cli.stuck_after));
+    if let Some(addr) = cli.metrics_addr {
+        metrics::serve(addr).await?;
+    }
+
+    match &cli.command {
+        Command::Tool { name, json } => println!("{}", render(&call_tool(name, json).await?)),
+        Command::Mcp => {
+            let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+            McpServer::new()
+                .await
+                .serve(stdin, tokio::io::stdout())
+                .await?
+        }
+        Command::Audit {
+            chain,
+            account,
+            tool,
+            user,
+            since,
+            until,
+        } => {
+            let filter = AuditFilter {
+                chain: chain.clone(),
+                account: *account,
+                tool: tool.clone(),
+                user: user.clone(),
+                since: *since,
+                until: *until,
+            };
+            for record in audit::query(&cli.audit_log, &filter)? {
+                println!("{}", serde_json::to_string(&record)?);
+            }
+        }
+        Command::Proposals { action } => {
+            let store = ProposalStore::default();
+            match action {
+                ProposalCommand::List { status } => {
+                    for proposal in store.list(*status)? {
+                        println!("{}", serde_json::to_string(&proposal)?);
+                    }
+                }
+                ProposalCommand::Approve { id, users } => {
+                    let store = match users {
+                        Some(path) => store.with_users(Users::load(path)?),
+                        None => store,
+                    };
+                    println!("{}", store.approve(id).await?)
+                }
+                ProposalCommand::Reject { id } => {
+                    let proposal = store.reject(id)?;
+                    println!("rejected {}: {}", proposal.id, proposal.output.summary)
+                }
+            }
+        }
+        _ => {
+            let config = llm_config(&cli)?;
+            if cli.router {
+                run_agent(&cli.command, &config.model, Router::from_config(&config)?).await?
+            } else {
+                run_agent(&cli.command, &config.model, config.build_agent(&EthAgent)?).await?
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_agent(
+    command: &Command,
+    model: &str,
+    agent: impl Prompt + Chat + 'static,
+) -> Result<()> {
+    match command {
+        Command::Prompt { text } => {
+            let response = audit::scope(model, text, agent.prompt(text))
+                .instrument(info_span!("agent_prompt", model))
+                .await?;
+            println!("{}", render(&response))
+        }
+        Command::Repl => {
+            tokio::spawn(TX_TRACKER.watch_stuck(STUCK_CHECK_INTERVAL));
+            repl::run(&agent, model).await?
+        }
+        Command::Serve { addr, users } => {
+            tokio::spawn(TX_TRACKER.watch_stuck(STUCK_CHECK_INTERVAL));
+            let users = match users {
+                Some(path) => Some(Users::load(path)?),
+                None => None,
+            };
+            server::serve(*addr, agent, model, users).await?
+        }
+        Command::Bot {
+            token,
+            api_url,
+            allowed_chats,
+        } => {
+            set_approval_required(true);
+            tokio::spawn(TX_TRACKER.watch_stuck(STUCK_CHECK_INTERVAL));
+            let transport = TelegramTransport::new(api_url, token)?;
+            let allowed_chats = allowed_chats.iter().cloned().collect();
+            Bot::new(agent, model, transport, allowed_chats)
+                .run()
+                .await?
+        }
+        Command::Tool { .. } | Command::Mcp | Command::Audit { .. } | Command::Proposals { .. } => {
+            unreachable!("tools, MCP, the audit log and proposals are used without an agent")
+        }
+    }
+    Ok(())
+}
+
+fn llm_config(cli: &Cli) -> Result<LlmConfig> {
+    let mut config = LlmConfigs::load(&cli.llm_config)?.get(cli.llm.as_deref())?;
+    if let Some(model) = &cli.model {
+        config.model = model.clone();
+    }
+    if let Some(provider_url) = &cli.provider_url {
+        config.base_url = Some(provider_url.clone());
+    }
+    Ok(config)
+}
+
+async fn call_tool(name: &str, json: &str) -> Result<String> {
+    let toolset = toolset();
+    if !toolset.contains(name) {
+        return Err(anyhow!(
+            "unknown tool '{}', available: {}",
+            name,
+            TOOL_NAMES.join(", ")
+        ));
+    }
+    Ok(toolset.call(name, json.to_string()).await?)
+}
+
+// A date means the start of that day in UTC.
+fn parse_since(s: &str) -> Result<DateTime<Utc>> {
+    parse_time(s, 0)
+}
+
+// A date means the end of that day, so `--until` includes it.
+fn parse_until(s: &str) -> Result<DateTime<Utc>> {
+    parse_time(s, 1)
+}
+
+fn parse_time(s: &str, date_offset_days: i64) -> Result<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        let start = date.and_time(NaiveTime::MIN).and_utc();
+        return Ok(start + Duration::days(date_offset_days));
+    }
+    Ok(DateTime::parse_from_rfc3339(s)
+        .map_err(|e| anyhow!("expected YYYY-MM-DD or an RFC 3339 time: {}", e))?
+        .with_timezone(&Utc))
+}
+
+#[tokio::test]
+async fn test_call_tool() {
+    let output = call_tool("list_tokens", r#"{"chain": "base", "symbol": "WETH"}"#)
+        .await
+        .unwrap();
+    assert!(output.contains("0x4200000000000000000000000000000000000006"));
+    assert!(call_tool("nope", "{}").await.is_err());
+}
@@ -1,30 +1,52 @@
 use alloy::{
-    network::{EthereumWallet, TransactionBuilder},
-    primitives::{utils::parse_ether, Address, TxHash, B256},
-    providers::{Provider, ProviderBuilder, RootProvider},
+    network::TransactionBuilder,
+    primitives::{Address, U256},
     rpc::types::TransactionRequest,
-    signers::local::PrivateKeySigner,
-    transports::http::{Client, Http},
 };
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use rig::{completion::ToolDefinition, tool::Tool};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::{str::FromStr, sync::Arc};
-
-use crate::chains::get_chain_info;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::{
+    chains::get_chain_info,
+    fee::{estimate_cost, FeeTier},
+    metrics,
+    proposal::propose,
+    settings::{approval_required, dry_run},
+    tool_schema::{
+        address_schema, amount_schema, chain_schema, parameters, parse_amount, whole_units,
+    },
+    tx_output::TxOutput,
+    tx_tracker::TX_TRACKER,
+    wallet::send_transaction,
+};
 
 const MAX_AMOUNT: u128 = 10u128; //maximum amount in ETH
 
-#[derive(Deserialize)]
+pub const PREAMBLE: &str = "You are a transfer agent here to help the user perform ETH transfers.";
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct ETHTransferArgs {
+    /// The chain name, alias or chain ID, such as arbitrum
+    #[schemars(schema_with = "chain_schema")]
     chain: String,
+    /// The receiving address
+    #[schemars(schema_with = "address_schema")]
     to_address: String,
+    /// The amount of ETH to transfer
+    #[schemars(schema_with = "amount_schema")]
     amount: String,
+    /// How fast the transaction should be included: slow pays the least, fast the most
+    #[serde(default)]
+    speed: FeeTier,
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error("ETH transfer error")]
+#[error("ETH transfer error: {message}")]
 pub struct ETHTransferError {
     message: String,
 }
@@ -36,46 +58,30 @@ impl Tool for ETHTransfer {
 
     type Error = ETHTransferError;
     type Args = ETHTransferArgs;
-    type Output = String;
+    type Output = TxOutput;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
             name: "eth_transfer".to_string(),
             description: "Transfer ETH to a specific address".to_string(),
-            parameters: json!({
-                "type": "object",
-                "properties": {
-                    "chain": {
-                        "type": "string",
-                        "description": "The chain name, such as arbitrum"
-                    },
-                    "to_address": {
-                        "type": "string",
-                        "description": "The receiving address"
-                    },
-                    "amount": {
-                        "type": "string",
-                        "description": "The amount of ETH to transfer"
-                    }
-                }
-            }),
+            parameters: parameters::<ETHTransferArgs>(),
         }
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
         let chain_name = args.chain;
-        let to_address = Address::from_str(&args.to_address).unwrap();
-        let amount = u128::from_str(&args.amount).unwrap_or_default();
-        println!(
-            "chain_name: {}, to_address: {}, amount: {}",
-            chain_name, to_address, amount
-        );
-
-        if amount > MAX_AMOUNT {
-            println!(
-                "amount = {} exceeds the safe value = {}",
-                amount, MAX_AMOUNT
-            );
+        let to_address = Address::from_str(&args.to_address).map_err(|e| ETHTransferError {
+            message: format!("invalid to_address: {}", e),
+        })?;
+        let amount = args.amount;
+        info!(chain = %chain_name, %to_address, %amount, "eth transfer");
+        let value = parse_amount(&amount, 18).map_err(|e| ETHTransferError {
+            message: e.to_string(),
+        })?;
+
+        if value > whole_units(MAX_AMOUNT, 18) {
+            warn!(%amount, max = MAX_AMOUNT, "amount exceeds the safe value");
+            metrics::policy_rejection(Self::NAME, "amount_limit");
             return Err(ETHTransferError {
                 message: format!(
                     "amount = {} exceeds the safe value = {}",
@@ -85,15 +91,38 @@ impl Tool for ETHTransfer {
             });
         }
 
-        let provider_url = get_chain_info(&chain_name)
-            .ok_or(ETHTransferError {
-                message: "get_chain_info none".to_string(),
-            })?
-            .provider_url;
+        let chain_info = get_chain_info(&chain_name).map_err(|e| ETHTransferError {
+            message: e.to_string(),
+        })?;
+
+        let summary = format!("transfer {} ETH to {}", amount, to_address);
+        let output = TxOutput::new(
+            Self::NAME,
+            summary.clone(),
+            &chain_info,
+            to_address,
+            None,
+            amount,
+        )
+        .with_raw_amount(value);
+        let tx = transfer_eth_tx(to_address, value);
+        // The estimate only reads from the node, so dry runs get it too.
+        let output = output.with_gas_estimate(estimate_cost(&chain_info, &tx, args.speed).await);
+        if dry_run() {
+            return Ok(output);
+        }
+        if approval_required() {
+            return propose(output, tx, args.speed).map_err(|e| ETHTransferError {
+                message: format!("propose error: {:#}", e),
+            });
+        }
 
-        let result = transfer_eth(to_address, amount, provider_url).await;
+        let result = send_transaction(&chain_info, tx, args.speed).await;
         match result {
-            Ok(h) => Ok(h.to_string()),
+            Ok(broadcast) => {
+                TX_TRACKER.record_sent(&chain_info.chain, &broadcast, summary);
+                Ok(output.with_broadcast(&chain_info, broadcast))
+            }
             Err(e) => Err(ETHTransferError {
                 message: format!("transfer_eth error: {}", e),
             }),
@@ -101,89 +130,81 @@ impl Tool for ETHTransfer {
     }
 }
 
-async fn transfer_eth(
-    to_address: Address,
-    amount: u128,
-    provider_url: String,
-) -> std::result::Result<B256, anyhow::Error> {
-    // Read the private key from the environment variable
-    // let private_key = env::var("PRIVATE_KEY").unwrap();
-
-    // [RISK WARNING! Writing a private key in the code file is insecure behavior.]
-    // The following code is for testing only. Set up signer from private key, be aware of danger.
-    let private_key = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
-    let signer: PrivateKeySigner = private_key.parse().expect("parse PrivateKeySigner");
-    let wallet: EthereumWallet = EthereumWallet::from(signer.clone());
-
-    // Create a http client to the EVM chain network.
-    let provider: RootProvider<Http<Client>> =
-        ProviderBuilder::new().on_http(provider_url.parse().expect("parse l1_rpc to Url"));
-
-    // Create eth signer.
-    let signer = Arc::new(
-        ProviderBuilder::new()
-            .with_recommended_fillers()
-            .wallet(wallet)
-            .on_provider(provider.clone()),
-    );
-
-    // Sync send transfer call.
-    let tx_hash: std::result::Result<TxHash, anyhow::Error> = async move {
-        let handle = tokio::task::spawn_blocking(move || {
-            let result = tokio::runtime::Handle::current().block_on(async {
-                let tx = TransactionRequest::default()
-                    .with_to(to_address)
-                    .with_value(parse_ether(&amount.to_string()).unwrap_or_default());
-
-                // Send the transaction and listen for the transaction to be included.
-                signer.send_transaction(tx).await
-            });
-            result
-        });
-        match handle.await {
-            Ok(Ok(tx)) => Ok(tx.tx_hash().clone()),
-            Ok(Err(e)) => Err(anyhow!(format!("alloy rpc error: {}", e))), // sign_transaction
-            Err(e) => Err(anyhow!(format!("tokio exec error: {}", e))),    // spawn_blocking
-        }
-    }
-    .await;
-    tx_hash
+fn transfer_eth_tx(to_address: Address, value: U256) -> TransactionRequest {
+    TransactionRequest::default()
+        .with_to(to_address)
+        .with_value(value)
 }
 
 #[tokio::test]
 async fn test_transfer_eth() -> Result<()> {
+    use alloy::primitives::utils::parse_ether;
+
+    let anvil = crate::anvil_harness::AnvilHarness::spawn().await?;
     let to_address = Address::from_str("1CBd0109c7452926fC7cCf06e73aCC505A296cc7").unwrap();
-    let tx_hash = transfer_eth(to_address, 10, String::from("http://localhost:8545")).await;
-    println!("tx_hash:{}", tx_hash.unwrap().to_string());
+    let tx = transfer_eth_tx(to_address, parse_ether("10")?);
+    let broadcast = send_transaction(&get_chain_info(&anvil.chain)?, tx, FeeTier::Normal).await?;
+    println!("tx_hash:{}", broadcast.hash);
+    assert_eq!(broadcast.gas_used, Some(21000));
+    assert_eq!(anvil.eth_balance(to_address).await?, parse_ether("10")?);
     Ok(())
 }
 
 #[tokio::test]
 async fn test_run_eth() -> Result<()> {
-    use crate::chains::CHAIN_INFOS;
-    use rig::completion::Prompt;
-    use rig::providers::openai;
-
-    // Create OpenAI client and model
-    let openai_client = openai::Client::from_url("sk-xxxxx", "https://api.xxxxx.xx/");
-
-    //Qwen/Qwen2.5-32B-Instruct
-    //Qwen/Qwen2.5-72B-Instruct-128K
-    let transfer_agent = openai_client
-        .agent("Qwen/Qwen2.5-32B-Instruct")
-        .preamble("You are a transfer agent here to help the user perform ETH transfers.")
-        .context(&serde_json::to_string(&*CHAIN_INFOS).unwrap())
+    use crate::chains::chains_context;
+    use crate::mock_llm::{MockModel, MockStep};
+    use crate::registry_tools::{ListChains, ListTokens};
+    use alloy::primitives::utils::parse_ether;
+    use rig::{agent::AgentBuilder, completion::Prompt};
+    use serde_json::json;
+
+    let anvil = crate::anvil_harness::AnvilHarness::spawn().await?;
+    let to_address = Address::from_str("1CBd0109c7452926fC7cCf06e73aCC505A296cc7").unwrap();
+
+    // Scripted model: answers the prompt with the tool call a live model would make.
+    let model = MockModel::new(vec![MockStep::tool_call(
+        "eth_transfer",
+        json!({
+            "chain": anvil.chain,
+            "to_address": to_address.to_string(),
+            "amount": "0.5"
+        }),
+    )]);
+
+    let transfer_agent = AgentBuilder::new(model)
+        .preamble(PREAMBLE)
+        .context(&chains_context())
         .max_tokens(2048)
         .tool(ETHTransfer)
+        .tool(ListChains)
+        .tool(ListTokens)
         .build();
 
     // Prompt the agent and print the response
     println!("Transfer ETH");
-    println!(
-        "Transfer Agent: {}",
-        transfer_agent
-            .prompt("Transfer 10 ETH to 0x1CBd0109c7452926fC7cCf06e73aCC505A296cc7 on base")
-            .await?
-    );
+    let response = transfer_agent
+        .prompt(&format!(
+            "Transfer 0.5 ETH to {} on {}",
+            to_address, anvil.chain
+        ))
+        .await?;
+    println!("Transfer Agent: {}", response);
+    let output: TxOutput = serde_json::from_str(&response)?;
+    assert_eq!(output.status, crate::tx_output::TxStatus::Success);
+    assert_eq!(output.raw_amount, Some(parse_ether("0.5")?.to_string()));
+    assert_eq!(anvil.eth_balance(to_address).await?, parse_ether("0.5")?);
     Ok(())
 }
+
+#[tokio::test]
+async fn test_invalid_address() {
+    let args = ETHTransferArgs {
+        chain: "base".to_string(),
+        to_address: "not-an-address".to_string(),
+        amount: "0.1".to_string(),
+        speed: FeeTier::Normal,
+    };
+    let error = ETHTransfer.call(args).await.unwrap_err();
+    assert!(error.message.starts_with("invalid to_address"));
+}
@@ -0,0 +1,521 @@
+use std::{fmt, str::FromStr};
+
+use alloy::{
+    eips::BlockNumberOrTag,
+    primitives::{
+        utils::{format_ether, format_units},
+        Address, U256,
+    },
+    providers::{Provider, ProviderBuilder, RootProvider},
+    rpc::types::{FeeHistory, TransactionRequest},
+    transports::{
+        http::{Client, Http},
+        Transport,
+    },
+};
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{chains::ChainInfo, swap::quote_eth_to_erc20, telemetry::rpc, wallet::local_address};
+
+/// Blocks of fee history the priority fee is estimated from.
+const FEE_HISTORY_BLOCKS: u64 = 10;
+
+/// Percent of the fees of a pending transaction its replacement pays at least.
+/// Nodes only accept a replacement paying 10% more, the rest is headroom.
+const REPLACEMENT_PERCENT: u128 = 115;
+
+/// How quickly a transaction should be included, and so how much it pays.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeTier {
+    Slow,
+    #[default]
+    Normal,
+    Fast,
+}
+
+impl FeeTier {
+    const PERCENTILES: [f64; 3] = [10.0, 50.0, 90.0];
+
+    /// Index into [`FeeTier::PERCENTILES`]: the priority fee paid in recent blocks.
+    fn percentile_index(self) -> usize {
+        match self {
+            FeeTier::Slow => 0,
+            FeeTier::Normal => 1,
+            FeeTier::Fast => 2,
+        }
+    }
+
+    /// Headroom over the next base fee, in percent, so the transaction stays
+    /// valid while the base fee rises.
+    fn base_fee_percent(self) -> u128 {
+        match self {
+            FeeTier::Slow => 125,
+            FeeTier::Normal => 200,
+            FeeTier::Fast => 200,
+        }
+    }
+
+    /// Percent of `eth_gasPrice` paid on chains without EIP-1559.
+    fn gas_price_percent(self) -> u128 {
+        match self {
+            FeeTier::Slow => 100,
+            FeeTier::Normal => 110,
+            FeeTier::Fast => 125,
+        }
+    }
+}
+
+impl fmt::Display for FeeTier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FeeTier::Slow => "slow",
+            FeeTier::Normal => "normal",
+            FeeTier::Fast => "fast",
+        })
+    }
+}
+
+/// Fees for one transaction, in wei per gas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fees {
+    Eip1559 {
+        max_fee_per_gas: u128,
+        max_priority_fee_per_gas: u128,
+        /// Next block's base fee, what the transaction is expected to pay on top
+        /// of the priority fee.
+        base_fee_per_gas: u128,
+    },
+    Legacy {
+        gas_price: u128,
+    },
+}
+
+impl Fees {
+    /// Set the fees on `tx`; the gas filler then leaves them alone.
+    pub fn apply(self, tx: &mut TransactionRequest) {
+        match self {
+            Fees::Eip1559 {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+                ..
+            } => {
+                tx.max_fee_per_gas = Some(max_fee_per_gas);
+                tx.max_priority_fee_per_gas = Some(max_priority_fee_per_gas);
+            }
+            Fees::Legacy { gas_price } => tx.gas_price = Some(gas_price),
+        }
+    }
+
+    /// Fees high enough to replace `previous`, a pending transaction with the
+    /// same nonce: what `self` pays, but at least [`REPLACEMENT_PERCENT`] of
+    /// what `previous` offered.
+    pub fn replacing(self, previous: &TransactionRequest) -> Fees {
+        let bumped =
+            |fee: Option<u128>| (fee.unwrap_or_default() * REPLACEMENT_PERCENT).div_ceil(100);
+        match self {
+            Fees::Eip1559 {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+                base_fee_per_gas,
+            } => {
+                let priority_fee = max_priority_fee_per_gas.max(bumped(
+                    previous.max_priority_fee_per_gas.or(previous.gas_price),
+                ));
+                Fees::Eip1559 {
+                    max_fee_per_gas: max_fee_per_gas
+                        .max(bumped(previous.max_fee_per_gas.or(previous.gas_price)))
+                        .max(priority_fee),
+                    max_priority_fee_per_gas: priority_fee,
+                    base_fee_per_gas,
+                }
+            }
+            Fees::Legacy { gas_price } => Fees::Legacy {
+                gas_price: gas_price.max(bumped(previous.gas_price.or(previous.max_fee_per_gas))),
+            },
+        }
+    }
+
+    /// Most the transaction can pay per gas.
+    pub fn max_fee(self) -> u128 {
+        match self {
+            Fees::Eip1559 {
+                max_fee_per_gas, ..
+            } => max_fee_per_gas,
+            Fees::Legacy { gas_price } => gas_price,
+        }
+    }
+
+    /// Price per gas the transaction is expected to pay if included soon.
+    pub fn expected_price(self) -> u128 {
+        match self {
+            Fees::Eip1559 {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+                base_fee_per_gas,
+            } => max_fee_per_gas.min(base_fee_per_gas + max_priority_fee_per_gas),
+            Fees::Legacy { gas_price } => gas_price,
+        }
+    }
+}
+
+/// What a transaction is expected to cost before it is sent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GasEstimate {
+    pub gas: u64,
+    /// Gas times the expected price per gas, in wei.
+    pub fee_wei: String,
+    /// The fee in ETH.
+    pub fee: String,
+    /// The fee in the chain's stablecoin, e.g. "0.42 USDC", when the swap router can price it.
+    pub fee_stable: Option<String>,
+}
+
+impl fmt::Display for GasEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "~{} ETH", self.fee)?;
+        if let Some(stable) = &self.fee_stable {
+            write!(f, " (~{})", stable)?;
+        }
+        write!(f, " for {} gas", self.gas)
+    }
+}
+
+/// Estimate gas and fees for sending `tx` from the local account at `speed`, with
+/// the fee priced in the chain's stablecoin. Best effort: failures are logged and
+/// give `None`, the transaction itself reports what is wrong with it.
+pub async fn estimate_cost(
+    chain_info: &ChainInfo,
+    tx: &TransactionRequest,
+    speed: FeeTier,
+) -> Option<GasEstimate> {
+    let estimate = estimate_fee(chain_info, tx.clone(), speed).await;
+    let (gas, fee_wei) = estimate
+        .inspect_err(|e| warn!(chain = chain_info.chain, "gas estimate failed: {:#}", e))
+        .ok()?;
+    let fee_stable = match stable_price(chain_info, fee_wei).await {
+        Ok(price) => price,
+        Err(e) => {
+            warn!(chain = chain_info.chain, "fee not priced: {:#}", e);
+            None
+        }
+    };
+    Some(GasEstimate {
+        gas,
+        fee_wei: fee_wei.to_string(),
+        fee: trim_decimal(&format_ether(fee_wei)),
+        fee_stable,
+    })
+}
+
+// Gas units and the expected fee in wei.
+async fn estimate_fee(
+    chain_info: &ChainInfo,
+    mut tx: TransactionRequest,
+    speed: FeeTier,
+) -> Result<(u64, U256)> {
+    let provider: RootProvider<Http<Client>> =
+        ProviderBuilder::new().on_http(chain_info.provider_url.parse()?);
+    let chain = chain_info.chain.clone();
+    let max_fee = chain_info.max_fee_per_gas()?;
+    tx.from = Some(local_address());
+
+    let gas = rpc(&chain, "estimate_gas", provider.estimate_gas(&tx))
+        .await
+        .map_err(|e| anyhow!("alloy rpc error: {}", e))?;
+    let fees = suggest_fees(&provider, &chain, speed, max_fee).await?;
+    Ok((gas, U256::from(gas) * U256::from(fees.expected_price())))
+}
+
+// `fee_wei` swapped into the configured stablecoin through WETH, `None` without one.
+async fn stable_price(chain_info: &ChainInfo, fee_wei: U256) -> Result<Option<String>> {
+    let Some(symbol) = &chain_info.stablecoin else {
+        return Ok(None);
+    };
+    let token = |symbol: &str| {
+        chain_info
+            .tokens
+            .get(symbol)
+            .and_then(|address| Address::from_str(address).ok())
+            .ok_or_else(|| anyhow!("no {} configured on {}", symbol, chain_info.chain))
+    };
+    let path = vec![token("WETH")?, token(symbol)?];
+    let quote = quote_eth_to_erc20(
+        Address::from_str(&chain_info.swap_router)?,
+        fee_wei,
+        path,
+        chain_info.chain.clone(),
+        chain_info.provider_url.clone(),
+    )
+    .await?;
+    let amount: f64 = quote.parse()?;
+    Ok(Some(match amount {
+        a if a > 0.0 && a < 0.01 => format!("<0.01 {}", symbol),
+        a => format!("{:.2} {}", a, symbol),
+    }))
+}
+
+// "0.000021000000000000" -> "0.000021"
+pub fn trim_decimal(value: &str) -> String {
+    match value.contains('.') {
+        true => value
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string(),
+        false => value.to_string(),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{what} of {} gwei exceeds the {chain} fee cap of {} gwei", gwei(*.fee), gwei(*.cap))]
+pub struct FeeCapExceeded {
+    what: &'static str,
+    chain: String,
+    fee: u128,
+    cap: u128,
+}
+
+fn gwei(wei: u128) -> String {
+    format_units(wei, "gwei").unwrap_or_else(|_| wei.to_string())
+}
+
+/// Fees for `tier` from recent blocks, capped at `max_fee` per gas. Chains without
+/// a base fee get a legacy gas price.
+pub async fn suggest_fees<P: Provider<T>, T: Transport + Clone>(
+    provider: &P,
+    chain: &str,
+    tier: FeeTier,
+    max_fee: Option<u128>,
+) -> Result<Fees> {
+    let fees = cap(current_fees(provider, chain, tier).await?, chain, max_fee)?;
+    info!(chain, %tier, ?fees, "fees");
+    Ok(fees)
+}
+
+/// Like [`suggest_fees`], but enough to replace `previous`, which is still pending.
+pub async fn suggest_replacement_fees<P: Provider<T>, T: Transport + Clone>(
+    provider: &P,
+    chain: &str,
+    tier: FeeTier,
+    max_fee: Option<u128>,
+    previous: &TransactionRequest,
+) -> Result<Fees> {
+    let fees = cap_replacement(
+        current_fees(provider, chain, tier).await?,
+        previous,
+        chain,
+        max_fee,
+    )?;
+    info!(chain, %tier, ?fees, "replacement fees");
+    Ok(fees)
+}
+
+/// [`Fees::replacing`] under the cap. A cap that leaves less than the bump over
+/// `previous` is an error naming the fee the replacement needs.
+fn cap_replacement(
+    fees: Fees,
+    previous: &TransactionRequest,
+    chain: &str,
+    max_fee: Option<u128>,
+) -> Result<Fees, FeeCapExceeded> {
+    let capped = cap(fees.replacing(previous), chain, max_fee)?;
+    // The cap only lowers the max fee, and only of EIP-1559 fees.
+    let Fees::Eip1559 {
+        base_fee_per_gas, ..
+    } = fees
+    else {
+        return Ok(capped);
+    };
+    // The least the node takes: the bump over `previous` with no fees of our own.
+    let needed = Fees::Eip1559 {
+        max_fee_per_gas: 0,
+        max_priority_fee_per_gas: 0,
+        base_fee_per_gas,
+    }
+    .replacing(previous)
+    .max_fee();
+    match max_fee {
+        Some(max_fee) if capped.max_fee() < needed => Err(FeeCapExceeded {
+            what: "replacement max fee",
+            chain: chain.to_string(),
+            fee: needed,
+            cap: max_fee,
+        }),
+        _ => Ok(capped),
+    }
+}
+
+// Chains whose fee history has no base fee get a legacy gas price. A failed
+// request is an error, not a sign the chain lacks EIP-1559.
+async fn current_fees<P: Provider<T>, T: Transport + Clone>(
+    provider: &P,
+    chain: &str,
+    tier: FeeTier,
+) -> Result<Fees> {
+    let history = rpc(
+        chain,
+        "fee_history",
+        provider.get_fee_history(
+            FEE_HISTORY_BLOCKS,
+            BlockNumberOrTag::Latest,
+            &FeeTier::PERCENTILES,
+        ),
+    )
+    .await
+    .map_err(|e| anyhow!("alloy rpc error: {}", e))?;
+    Ok(match eip1559_fees(&history, tier) {
+        Some(fees) => fees,
+        None => Fees::Legacy {
+            gas_price: rpc(chain, "gas_price", provider.get_gas_price())
+                .await
+                .map_err(|e| anyhow!("alloy rpc error: {}", e))?
+                * tier.gas_price_percent()
+                / 100,
+        },
+    })
+}
+
+/// `None` when the chain reports no base fee, i.e. does not support EIP-1559.
+fn eip1559_fees(history: &FeeHistory, tier: FeeTier) -> Option<Fees> {
+    let base_fee = history.next_block_base_fee().filter(|fee| *fee > 0)?;
+    let mut rewards: Vec<u128> = history
+        .reward
+        .iter()
+        .flatten()
+        .filter_map(|block| block.get(tier.percentile_index()).copied())
+        .collect();
+    rewards.sort_unstable();
+    let priority_fee = rewards.get(rewards.len() / 2).copied().unwrap_or_default();
+    Some(Fees::Eip1559 {
+        max_fee_per_gas: base_fee * tier.base_fee_percent() / 100 + priority_fee,
+        max_priority_fee_per_gas: priority_fee,
+        base_fee_per_gas: base_fee,
+    })
+}
+
+/// Lower fees to `max_fee`, unless what inclusion costs right now is already above it.
+fn cap(fees: Fees, chain: &str, max_fee: Option<u128>) -> Result<Fees, FeeCapExceeded> {
+    let Some(max_fee) = max_fee else {
+        return Ok(fees);
+    };
+    let exceeded = |what, fee| FeeCapExceeded {
+        what,
+        chain: chain.to_string(),
+        fee,
+        cap: max_fee,
+    };
+    match fees {
+        Fees::Legacy { gas_price } if gas_price > max_fee => Err(exceeded("gas price", gas_price)),
+        Fees::Legacy { .. } => Ok(fees),
+        Fees::Eip1559 {
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+            base_fee_per_gas,
+        } => {
+            let needed = base_fee_per_gas + max_priority_fee_per_gas;
+            if needed > max_fee {
+                return Err(exceeded("base fee plus priority fee", needed));
+            }
+            Ok(Fees::Eip1559 {
+                max_fee_per_gas: max_fee_per_gas.min(max_fee),
+                max_priority_fee_per_gas,
+                base_fee_per_gas,
+            })
+        }
+    }
+}
+
+#[test]
+fn test_fee_tiers() {
+    let gwei = 1_000_000_000u128;
+    let history = FeeHistory {
+        base_fee_per_gas: vec![10 * gwei, 12 * gwei, 20 * gwei],
+        reward: Some(vec![
+            vec![gwei, 2 * gwei, 5 * gwei],
+            vec![gwei, 3 * gwei, 4 * gwei],
+        ]),
+        ..Default::default()
+    };
+    assert_eq!(
+        eip1559_fees(&history, FeeTier::Slow),
+        Some(Fees::Eip1559 {
+            max_fee_per_gas: 26 * gwei,
+            max_priority_fee_per_gas: gwei,
+            base_fee_per_gas: 20 * gwei,
+        })
+    );
+    let fast = eip1559_fees(&history, FeeTier::Fast).unwrap();
+    assert_eq!(
+        fast,
+        Fees::Eip1559 {
+            max_fee_per_gas: 45 * gwei,
+            max_priority_fee_per_gas: 5 * gwei,
+            base_fee_per_gas: 20 * gwei,
+        }
+    );
+    assert_eq!(fast.expected_price(), 25 * gwei);
+
+    // A replacement pays at least 15% more than the transaction it replaces.
+    let mut pending = TransactionRequest::default();
+    fast.apply(&mut pending);
+    let slow = eip1559_fees(&history, FeeTier::Slow).unwrap();
+    assert_eq!(
+        slow.replacing(&pending),
+        Fees::Eip1559 {
+            max_fee_per_gas: 51_750_000_000,
+            max_priority_fee_per_gas: 5_750_000_000,
+            base_fee_per_gas: 20 * gwei,
+        }
+    );
+
+    // A cap below the bump over the pending transaction would only get the
+    // replacement rejected.
+    assert_eq!(
+        cap_replacement(slow, &pending, "base", None).unwrap(),
+        slow.replacing(&pending)
+    );
+    let e = cap_replacement(slow, &pending, "base", Some(30 * gwei)).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "replacement max fee of 51.750000000 gwei exceeds the base fee cap of 30.000000000 gwei"
+    );
+
+    // Clamped to the cap as long as the base fee and priority fee fit under it.
+    let capped = cap(fast, "base", Some(30 * gwei)).unwrap();
+    assert_eq!(
+        capped,
+        Fees::Eip1559 {
+            max_fee_per_gas: 30 * gwei,
+            max_priority_fee_per_gas: 5 * gwei,
+            base_fee_per_gas: 20 * gwei,
+        }
+    );
+    let mut tx = TransactionRequest::default();
+    capped.apply(&mut tx);
+    assert_eq!(tx.max_fee_per_gas, Some(30 * gwei));
+    let e = cap(fast, "base", Some(24 * gwei)).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "base fee plus priority fee of 25.000000000 gwei exceeds the base fee cap of 24.000000000 gwei"
+    );
+
+    // Chains without a base fee fall back to legacy pricing.
+    let legacy = FeeHistory {
+        base_fee_per_gas: vec![0, 0],
+        ..Default::default()
+    };
+    assert_eq!(eip1559_fees(&legacy, FeeTier::Normal), None);
+    assert_eq!(trim_decimal("0.000021000000000000"), "0.000021");
+    assert_eq!(trim_decimal("1.000000000000000000"), "1");
+    assert!(cap(
+        Fees::Legacy {
+            gas_price: 2 * gwei
+        },
+        "bsc",
+        Some(gwei)
+    )
+    .is_err());
+}
@@ -1,4 +1,3 @@
-
 #[tokio::test]
 
 async fn gen_agent_tool() {
@@ -8,7 +7,6 @@ async fn gen_agent_tool() {
         loaders::FileLoader,
         providers::openai::{self},
     };
-    use std::env;
     use std::{fs, path::Path};
 
     // let cargo_home = env::var("CARGO_HOME").unwrap(); // default ~/.cargo
@@ -21,7 +19,7 @@ async fn gen_agent_tool() {
 
     // Check if a file exists
     if Path::new(&file_path).exists() {
-        match fs::read_to_string(file_path.clone()) {
+        match fs::read_to_string(file_path) {
             Ok(content) => {
                 println!("The file contents are as follows:\n{}", content);
             }
@@ -46,7 +44,7 @@ async fn gen_agent_tool() {
     // let data = FileLoader::with_dir("cargo/registry/src/index.crates.io-6f17d22bba15001f/rig-core-0.6.0/examples").unwrap();
     // .cargo/registry/src/index.crates.io-6f17d22bba15001f/rig-core-0.6.0/src/loaders/file.rs
     // .cargo/registry/src/index.crates.io-6f17d22bba15001f/rig-core-0.6.0/examples/agent_with_context.rs
-    let examples = FileLoader::with_glob(&file_path)
+    let examples = FileLoader::with_glob(file_path)
         .unwrap()
         .read_with_path()
         .ignore_errors()
@@ -0,0 +1,97 @@
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde_json::Value;
+use tracing::{field, info_span, warn, Instrument};
+
+use crate::{
+    audit::AuditRecord,
+    metrics,
+    tool_schema::validate,
+    users::{self, PermissionError},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum GuardError<E> {
+    #[error("invalid arguments: {0}")]
+    InvalidArgs(String),
+    #[error("{0}")]
+    NotPermitted(#[source] PermissionError),
+    #[error(transparent)]
+    Tool(E),
+}
+
+/// Wraps a tool the model can call: arguments are checked against the schema in
+/// the tool's definition and the current user's permissions before they reach
+/// `call`, and every call is audited.
+pub struct Guarded<T>(pub T);
+
+impl<T: Tool> Tool for Guarded<T> {
+    const NAME: &'static str = T::NAME;
+
+    type Error = GuardError<T::Error>;
+    type Args = Value;
+    type Output = T::Output;
+
+    async fn definition(&self, prompt: String) -> ToolDefinition {
+        self.0.definition(prompt).await
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let span = info_span!("tool_call", tool = T::NAME, outcome = field::Empty);
+        self.guarded_call(args).instrument(span).await
+    }
+}
+
+impl<T: Tool> Guarded<T> {
+    async fn guarded_call(&self, args: Value) -> Result<T::Output, GuardError<T::Error>> {
+        let span = tracing::Span::current();
+        let record = AuditRecord::new(T::NAME, args.clone());
+        let definition = self.0.definition(String::new()).await;
+        let permitted = users::current().map_or(Ok(()), |user| {
+            user.check_tool(T::NAME)?;
+            match args.get("chain").and_then(Value::as_str) {
+                Some(chain) => user.check_chain(chain),
+                None => Ok(()),
+            }
+        });
+        if let Err(e) = permitted {
+            span.record("outcome", "rejected");
+            metrics::tool_invocation(T::NAME, "rejected");
+            metrics::policy_rejection(T::NAME, e.reason());
+            warn!(error = %e, "not permitted");
+            record.rejected(e.to_string()).finish();
+            return Err(GuardError::NotPermitted(e));
+        }
+
+        let parsed = validate(&definition.parameters, &args)
+            .and_then(|()| serde_json::from_value(args).map_err(|e| e.to_string()));
+        let args = match parsed {
+            Ok(args) => args,
+            Err(e) => {
+                span.record("outcome", "rejected");
+                metrics::tool_invocation(T::NAME, "rejected");
+                metrics::policy_rejection(T::NAME, "invalid_arguments");
+                warn!(error = %e, "invalid arguments");
+                record.rejected(e.clone()).finish();
+                return Err(GuardError::InvalidArgs(e));
+            }
+        };
+
+        match self.0.call(args).await {
+            Ok(output) => {
+                let value = serde_json::to_value(&output).unwrap_or_default();
+                span.record("outcome", "ok");
+                metrics::tool_invocation(T::NAME, "ok");
+                metrics::tool_output(&value);
+                record.with_output(&value).finish();
+                Ok(output)
+            }
+            Err(e) => {
+                span.record("outcome", "failed");
+                metrics::tool_invocation(T::NAME, "failed");
+                warn!(error = %e, "tool failed");
+                record.failed(e.to_string()).finish();
+                Err(GuardError::Tool(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use rig::{
+    agent::{Agent, AgentBuilder},
+    completion::{Chat, CompletionModel, Message, Prompt, PromptError},
+    providers::{anthropic, gemini, openai},
+};
+use serde::Deserialize;
+
+use crate::{
+    metrics::{Metered, TokenUsage},
+    mock_llm::MockModel,
+};
+
+const OLLAMA_BASE_URL: &str = "http://localhost:11434/v1";
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// OpenAI or any OpenAI-compatible server, selected with `base_url`.
+    OpenAI,
+    Anthropic,
+    Gemini,
+    /// Local Ollama through its OpenAI-compatible endpoint, no API key needed.
+    Ollama,
+    /// Replays the scripted responses in `script`, for offline tests.
+    Mock,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct LlmConfig {
+    pub kind: ProviderKind,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    pub model: String,
+    /// Name of the environment variable holding the API key.
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u64,
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    /// Script file for the mock provider.
+    #[serde(default)]
+    pub script: Option<PathBuf>,
+}
+
+fn default_max_tokens() -> u64 {
+    2048
+}
+
+/// `configs/llm.json`: named provider entries and the one used when none is selected.
+#[derive(Debug, Deserialize)]
+pub struct LlmConfigs {
+    pub default: String,
+    pub providers: HashMap<String, LlmConfig>,
+}
+
+impl LlmConfigs {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("read llm config {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("parse llm config {}", path.display()))
+    }
+
+    pub fn get(&self, name: Option<&str>) -> Result<LlmConfig> {
+        let name = name.unwrap_or(&self.default);
+        self.providers.get(name).cloned().ok_or_else(|| {
+            let mut names: Vec<_> = self.providers.keys().map(String::as_str).collect();
+            names.sort();
+            anyhow!(
+                "unknown llm provider '{}', available: {}",
+                name,
+                names.join(", ")
+            )
+        })
+    }
+}
+
+/// Describes an agent independently of the provider it runs on.
+pub trait AgentSpec {
+    fn build<M: CompletionModel>(&self, builder: AgentBuilder<M>) -> Agent<M>;
+}
+
+/// An agent on any of the supported providers.
+pub enum LlmAgent {
+    OpenAI(Agent<Metered<openai::CompletionModel>>),
+    Anthropic(Agent<Metered<anthropic::completion::CompletionModel>>),
+    Gemini(Agent<Metered<gemini::completion::CompletionModel>>),
+    Mock(Agent<Metered<MockModel>>),
+}
+
+impl LlmConfig {
+    fn api_key(&self) -> Result<String> {
+        match (&self.api_key_env, self.kind) {
+            (Some(var), _) => env::var(var).map_err(|_| anyhow!("{} is not set", var)),
+            (None, ProviderKind::Ollama) => Ok("ollama".to_string()),
+            (None, ProviderKind::Mock) => Ok(String::new()),
+            (None, _) => Err(anyhow!("api_key_env is required for {:?}", self.kind)),
+        }
+    }
+
+    // Agents count the tokens the provider reports under the configured model name.
+    fn apply<M>(&self, model: M) -> AgentBuilder<Metered<M>>
+    where
+        M: CompletionModel,
+        M::Response: TokenUsage,
+    {
+        let builder =
+            AgentBuilder::new(Metered::new(model, &self.model)).max_tokens(self.max_tokens);
+        match self.temperature {
+            Some(t) => builder.temperature(t),
+            None => builder,
+        }
+    }
+
+    pub fn build_agent(&self, spec: &impl AgentSpec) -> Result<LlmAgent> {
+        let api_key = self.api_key()?;
+        let agent = match self.kind {
+            ProviderKind::OpenAI | ProviderKind::Ollama => {
+                let base_url = self.base_url.as_deref().unwrap_or(match self.kind {
+                    ProviderKind::Ollama => OLLAMA_BASE_URL,
+                    _ => "https://api.openai.com/v1",
+                });
+                let client = openai::Client::from_url(&api_key, base_url);
+                LlmAgent::OpenAI(spec.build(self.apply(client.completion_model(&self.model))))
+            }
+            ProviderKind::Anthropic => {
+                let mut client = anthropic::ClientBuilder::new(&api_key);
+                if let Some(base_url) = &self.base_url {
+                    client = client.base_url(base_url);
+                }
+                LlmAgent::Anthropic(
+                    spec.build(self.apply(client.build().completion_model(&self.model))),
+                )
+            }
+            ProviderKind::Gemini => {
+                let client = gemini::Client::new(&api_key);
+                LlmAgent::Gemini(spec.build(self.apply(client.completion_model(&self.model))))
+            }
+            ProviderKind::Mock => {
+                let script = self
+                    .script
+                    .as_deref()
+                    .ok_or(anyhow!("script is required for mock"))?;
+                let model = MockModel::from_file(script)?;
+                LlmAgent::Mock(spec.build(self.apply(model)))
+            }
+        };
+        Ok(agent)
+    }
+}
+
+impl Prompt for LlmAgent {
+    async fn prompt(&self, prompt: &str) -> Result<String, PromptError> {
+        self.chat(prompt, vec![]).await
+    }
+}
+
+impl Chat for LlmAgent {
+    async fn chat(&self, prompt: &str, chat_history: Vec<Message>) -> Result<String, PromptError> {
+        match self {
+            LlmAgent::OpenAI(agent) => agent.chat(prompt, chat_history).await,
+            LlmAgent::Anthropic(agent) => agent.chat(prompt, chat_history).await,
+            LlmAgent::Gemini(agent) => agent.chat(prompt, chat_history).await,
+            LlmAgent::Mock(agent) => agent.chat(prompt, chat_history).await,
+        }
+    }
+}
+
+#[test]
+fn test_llm_configs() {
+    let configs: LlmConfigs = serde_json::from_str(
+        r#"{
+            "default": "local",
+            "providers": {
+                "local": {"kind": "ollama", "model": "qwen2.5:14b"},
+                "claude": {"kind": "anthropic", "model": "claude-3-5-sonnet-latest", "api_key_env": "RIG_ETH_TEST_UNSET_KEY", "temperature": 0.2}
+            }
+        }"#,
+    )
+    .unwrap();
+
+    let local = configs.get(None).unwrap();
+    assert_eq!(local.kind, ProviderKind::Ollama);
+    assert_eq!(local.max_tokens, 2048);
+    assert_eq!(local.api_key().unwrap(), "ollama");
+
+    let claude = configs.get(Some("claude")).unwrap();
+    assert_eq!(claude.temperature, Some(0.2));
+    assert!(claude.api_key().is_err());
+    assert!(configs.get(Some("gpt")).is_err());
+}
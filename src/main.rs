@@ -1,35 +1,43 @@
+mod agent;
+#[cfg(test)]
+mod anvil_harness;
+mod audit;
+mod balance;
+mod bot;
+mod chains;
+mod cli;
 mod erc20_transfer;
 mod eth_transfer;
-mod swap;
-mod chains;
+mod fee;
 mod gen_tools;
-use erc20_transfer::ERC20Transfer;
+mod guard;
+mod llm;
+mod mcp;
+mod metrics;
+mod mock_llm;
+mod nonce;
+mod pending_tools;
+mod proposal;
+mod registry_tools;
+mod repl;
+mod router;
+mod server;
+mod settings;
+mod swap;
+mod telemetry;
+mod token_list;
+mod tool_schema;
+mod tx_output;
+mod tx_tracker;
+mod users;
+mod wallet;
 use anyhow::Result;
-use chains::CHAIN_INFOS;
-use rig::completion::Prompt;
-use rig::providers::openai;
+use clap::Parser;
+use cli::Cli;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Create OpenAI client and model
-    let openai_client = openai::Client::from_url("sk-xxxxx", "https://api.xxxxx.xx/");
-
-    // agent
-    let transfer_agent = openai_client
-        .agent("Qwen/Qwen2.5-32B-Instruct")
-        .preamble("You are a transfer agent here to help the user perform ERC20 token transfers.")
-        .context(&serde_json::to_string(&*CHAIN_INFOS).unwrap())
-        .max_tokens(2048)
-        .tool(ERC20Transfer)
-        .build();
-
-    // Prompt the agent and print the response
-    println!("Transfer ERC20 tokens");
-    println!(
-        "Transfer Agent: {}",
-        transfer_agent
-            .prompt("Transfer 10 USDC to 0x1CBd0109c7452926fC7cCf06e73aCC505A296cc7 on base")
-            .await?
-    );
-    Ok(())
+    let cli = Cli::parse();
+    let _telemetry = telemetry::init(cli.log_format, cli.otlp_endpoint.as_deref())?;
+    cli::run(cli).await
 }
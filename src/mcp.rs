@@ -0,0 +1,191 @@
+use anyhow::Result;
+use rig::{completion::ToolDefinition, tool::ToolSet};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::agent::{tool_definitions, toolset};
+
+/// Protocol revision this server implements.
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Model Context Protocol server over newline-delimited JSON-RPC, exposing the
+/// same guarded tools the agent uses: arguments are validated and audited,
+/// transactions are signed with the local account and respect dry-run and approval.
+pub struct McpServer {
+    tools: ToolSet,
+    definitions: Vec<ToolDefinition>,
+}
+
+impl McpServer {
+    pub async fn new() -> Self {
+        McpServer {
+            tools: toolset(),
+            definitions: tool_definitions().await,
+        }
+    }
+
+    /// Answer requests from `reader` on `writer` until the client closes its end.
+    pub async fn serve(
+        &self,
+        reader: impl AsyncBufRead + Unpin,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        let mut lines = reader.lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str(&line) {
+                Ok(message) => self.handle(message).await,
+                Err(e) => Some(error(Value::Null, PARSE_ERROR, e.to_string())),
+            };
+            if let Some(response) = response {
+                writer
+                    .write_all(format!("{}\n", response).as_bytes())
+                    .await?;
+                writer.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The response to a JSON-RPC message, `None` for notifications.
+    async fn handle(&self, message: Value) -> Option<Value> {
+        let method = message.get("method").and_then(Value::as_str);
+        let Some(id) = message.get("id").cloned() else {
+            if let Some(method) = method {
+                info!(method, "mcp notification");
+            }
+            return None;
+        };
+        let Some(method) = method else {
+            return Some(error(id, INVALID_REQUEST, "missing method"));
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "initialize" => {
+                let client = params.pointer("/clientInfo/name").and_then(Value::as_str);
+                info!(client, "mcp client connected");
+                Ok(json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }))
+            }
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": self.list_tools() })),
+            "tools/call" => self.call_tool(&params).await,
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error(id, code, message),
+        })
+    }
+
+    /// Tool definitions as MCP tools: the parameters schema is the input schema.
+    fn list_tools(&self) -> Vec<Value> {
+        self.definitions
+            .iter()
+            .map(|definition| {
+                json!({
+                    "name": definition.name,
+                    "description": definition.description,
+                    "inputSchema": definition.parameters,
+                })
+            })
+            .collect()
+    }
+
+    /// Tool failures are results with `isError` for the host's model to read,
+    /// only unknown tools are protocol errors.
+    async fn call_tool(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let Some(name) = params.get("name").and_then(Value::as_str) else {
+            return Err((INVALID_PARAMS, "missing tool name".to_string()));
+        };
+        if !self.tools.contains(name) {
+            return Err((INVALID_PARAMS, format!("unknown tool '{}'", name)));
+        }
+        let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+        let result = self
+            .tools
+            .call(name, arguments.to_string())
+            .instrument(info_span!("mcp_call", tool = name))
+            .await;
+        let (text, is_error) = match result {
+            Ok(output) => (output, false),
+            Err(e) => {
+                warn!(tool = name, error = %e, "mcp tool call failed");
+                (e.to_string(), true)
+            }
+        };
+        Ok(json!({
+            "content": [{ "type": "text", "text": text }],
+            "isError": is_error,
+        }))
+    }
+}
+
+fn error(id: Value, code: i64, message: impl Into<String>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.into() },
+    })
+}
+
+#[tokio::test]
+async fn test_mcp_stdio() {
+    let requests = [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+            "protocolVersion": PROTOCOL_VERSION, "capabilities": {},
+            "clientInfo": {"name": "test", "version": "0"}}}),
+        json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+        json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {
+            "name": "list_tokens", "arguments": {"chain": "base", "symbol": "USDC"}}}),
+        json!({"jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": {
+            "name": "eth_transfer", "arguments": {"chain": "base"}}}),
+        json!({"jsonrpc": "2.0", "id": 5, "method": "tools/call", "params": {"name": "bridge"}}),
+        json!({"jsonrpc": "2.0", "id": 6, "method": "resources/list"}),
+    ];
+    let input: String = requests.iter().map(|r| format!("{}\n", r)).collect();
+    let mut output = vec![];
+    McpServer::new()
+        .await
+        .serve(format!("{}not json\n", input).as_bytes(), &mut output)
+        .await
+        .unwrap();
+    let responses: Vec<Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    // Every request is answered, the notification is not.
+    assert_eq!(responses.len(), 7);
+    assert_eq!(responses[0]["result"]["protocolVersion"], PROTOCOL_VERSION);
+    let tools = responses[1]["result"]["tools"].as_array().unwrap();
+    assert_eq!(tools.len(), crate::agent::TOOL_NAMES.len());
+    let transfer = tools.iter().find(|t| t["name"] == "eth_transfer").unwrap();
+    assert_eq!(transfer["inputSchema"]["type"], "object");
+    assert_eq!(responses[2]["result"]["isError"], false);
+    assert!(responses[2]["result"]["content"][0]["text"]
+        .as_str()
+        .unwrap()
+        .contains("0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913"));
+    assert_eq!(responses[3]["result"]["isError"], true);
+    assert_eq!(responses[4]["error"]["code"], INVALID_PARAMS);
+    assert_eq!(responses[5]["error"]["code"], METHOD_NOT_FOUND);
+    assert_eq!(responses[6]["error"]["code"], PARSE_ERROR);
+}
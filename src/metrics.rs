@@ -0,0 +1,265 @@
+use std::{net::SocketAddr, time::Duration};
+
+use anyhow::{Context, Result};
+use axum::{http::header::CONTENT_TYPE, routing::get, Router};
+use once_cell::sync::Lazy;
+use prometheus::{
+    exponential_buckets, histogram_opts, opts, Encoder, HistogramVec, IntCounterVec, Registry,
+    TextEncoder,
+};
+use rig::{
+    completion::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse},
+    providers::{anthropic, gemini, openai},
+};
+use serde_json::Value;
+use tokio::net::TcpListener;
+
+struct Metrics {
+    registry: Registry,
+    tool_invocations: IntCounterVec,
+    policy_rejections: IntCounterVec,
+    rpc_duration: HistogramVec,
+    gas_used: IntCounterVec,
+    llm_tokens: IntCounterVec,
+    stuck_transactions: IntCounterVec,
+}
+
+static METRICS: Lazy<Metrics> = Lazy::new(|| {
+    let registry = Registry::new_custom(Some("rig_eth".to_string()), None).unwrap();
+    let metrics = Metrics {
+        tool_invocations: IntCounterVec::new(
+            opts!("tool_invocations_total", "Tool calls by tool and outcome"),
+            &["tool", "outcome"],
+        )
+        .unwrap(),
+        policy_rejections: IntCounterVec::new(
+            opts!(
+                "policy_rejections_total",
+                "Tool calls refused before anything was sent, by tool and reason"
+            ),
+            &["tool", "reason"],
+        )
+        .unwrap(),
+        rpc_duration: HistogramVec::new(
+            histogram_opts!(
+                "rpc_duration_seconds",
+                "Latency of RPC requests by chain, method and outcome",
+                exponential_buckets(0.01, 2.0, 12).unwrap()
+            ),
+            &["chain", "method", "outcome"],
+        )
+        .unwrap(),
+        gas_used: IntCounterVec::new(
+            opts!("gas_used_total", "Gas used by mined transactions, by chain"),
+            &["chain"],
+        )
+        .unwrap(),
+        llm_tokens: IntCounterVec::new(
+            opts!(
+                "llm_tokens_total",
+                "Tokens reported by the LLM provider, by model and kind (input or output)"
+            ),
+            &["model", "kind"],
+        )
+        .unwrap(),
+        stuck_transactions: IntCounterVec::new(
+            opts!(
+                "stuck_transactions_total",
+                "Sent transactions still pending after the stuck threshold, by chain"
+            ),
+            &["chain"],
+        )
+        .unwrap(),
+        registry,
+    };
+    let collectors: [Box<dyn prometheus::core::Collector>; 6] = [
+        Box::new(metrics.tool_invocations.clone()),
+        Box::new(metrics.policy_rejections.clone()),
+        Box::new(metrics.rpc_duration.clone()),
+        Box::new(metrics.gas_used.clone()),
+        Box::new(metrics.llm_tokens.clone()),
+        Box::new(metrics.stuck_transactions.clone()),
+    ];
+    for collector in collectors {
+        metrics.registry.register(collector).unwrap();
+    }
+    metrics
+});
+
+pub fn tool_invocation(tool: &str, outcome: &str) {
+    METRICS
+        .tool_invocations
+        .with_label_values(&[tool, outcome])
+        .inc();
+}
+
+pub fn policy_rejection(tool: &str, reason: &str) {
+    METRICS
+        .policy_rejections
+        .with_label_values(&[tool, reason])
+        .inc();
+}
+
+pub fn rpc_request(chain: &str, method: &str, ok: bool, elapsed: Duration) {
+    let outcome = if ok { "ok" } else { "error" };
+    METRICS
+        .rpc_duration
+        .with_label_values(&[chain, method, outcome])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Count the gas of a transaction tool output; outputs without `gas_used` are skipped.
+pub fn tool_output(output: &Value) {
+    if let (Some(chain), Some(gas_used)) = (
+        output.get("chain").and_then(Value::as_str),
+        output.get("gas_used").and_then(Value::as_u64),
+    ) {
+        METRICS
+            .gas_used
+            .with_label_values(&[chain])
+            .inc_by(gas_used);
+    }
+}
+
+pub fn stuck_transaction(chain: &str) {
+    METRICS.stuck_transactions.with_label_values(&[chain]).inc();
+}
+
+fn llm_tokens(model: &str, input: u64, output: u64) {
+    METRICS
+        .llm_tokens
+        .with_label_values(&[model, "input"])
+        .inc_by(input);
+    METRICS
+        .llm_tokens
+        .with_label_values(&[model, "output"])
+        .inc_by(output);
+}
+
+/// Everything recorded so far, in the Prometheus text format.
+pub fn encode() -> String {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buffer)
+        .expect("metrics encode");
+    String::from_utf8(buffer).expect("metrics are utf-8")
+}
+
+/// Bind `addr` and serve `GET /metrics` in the background. Returns the bound address.
+pub async fn serve(addr: SocketAddr) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("bind metrics endpoint {}", addr))?;
+    let local_addr = listener.local_addr()?;
+    let app = Router::new().route(
+        "/metrics",
+        get(|| async { ([(CONTENT_TYPE, prometheus::TEXT_FORMAT)], encode()) }),
+    );
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            tracing::error!("metrics endpoint stopped: {}", e);
+        }
+    });
+    Ok(local_addr)
+}
+
+/// Input and output tokens of a provider response, when the provider reports them.
+pub trait TokenUsage {
+    fn token_usage(&self) -> Option<(u64, u64)>;
+}
+
+impl TokenUsage for openai::CompletionResponse {
+    fn token_usage(&self) -> Option<(u64, u64)> {
+        self.usage.as_ref().map(|u| {
+            let input = u.prompt_tokens as u64;
+            (input, (u.total_tokens as u64).saturating_sub(input))
+        })
+    }
+}
+
+impl TokenUsage for anthropic::completion::CompletionResponse {
+    fn token_usage(&self) -> Option<(u64, u64)> {
+        Some((self.usage.input_tokens, self.usage.output_tokens))
+    }
+}
+
+impl TokenUsage for gemini::completion::gemini_api_types::GenerateContentResponse {
+    fn token_usage(&self) -> Option<(u64, u64)> {
+        self.usage_metadata.as_ref().map(|u| {
+            (
+                u.prompt_token_count.max(0) as u64,
+                u.candidates_token_count.max(0) as u64,
+            )
+        })
+    }
+}
+
+impl TokenUsage for () {
+    fn token_usage(&self) -> Option<(u64, u64)> {
+        None
+    }
+}
+
+/// Completion model that counts the tokens of every response it returns.
+#[derive(Clone)]
+pub struct Metered<M> {
+    model: M,
+    name: String,
+}
+
+impl<M> Metered<M> {
+    pub fn new(model: M, name: &str) -> Self {
+        Metered {
+            model,
+            name: name.to_string(),
+        }
+    }
+}
+
+impl<M> CompletionModel for Metered<M>
+where
+    M: CompletionModel,
+    M::Response: TokenUsage,
+{
+    type Response = M::Response;
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<M::Response>, CompletionError> {
+        let response = self.model.completion(request).await?;
+        if let Some((input, output)) = response.raw_response.token_usage() {
+            llm_tokens(&self.name, input, output);
+        }
+        Ok(response)
+    }
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    tool_invocation("eth_transfer", "ok");
+    policy_rejection("eth_transfer", "amount_limit");
+    rpc_request("base", "send", false, Duration::from_millis(30));
+    tool_output(&serde_json::json!({"chain": "base", "gas_used": 21000}));
+    llm_tokens("gpt-4o", 120, 30);
+
+    let addr = serve("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /metrics HTTP/1.0\r\n\r\n")
+        .await
+        .unwrap();
+    let mut body = String::new();
+    stream.read_to_string(&mut body).await.unwrap();
+    assert!(body.starts_with("HTTP/1.0 200 OK"));
+    assert!(body.contains(r#"rig_eth_tool_invocations_total{outcome="ok",tool="eth_transfer"}"#));
+    assert!(body
+        .contains(r#"rig_eth_policy_rejections_total{reason="amount_limit",tool="eth_transfer"}"#));
+    assert!(body.contains(
+        r#"rig_eth_rpc_duration_seconds_count{chain="base",method="send",outcome="error"}"#
+    ));
+    assert!(body.contains(r#"rig_eth_gas_used_total{chain="base"}"#));
+    assert!(body.contains(r#"rig_eth_llm_tokens_total{kind="output",model="gpt-4o"} 30"#));
+}
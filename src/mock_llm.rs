@@ -0,0 +1,126 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result};
+use rig::completion::{
+    CompletionError, CompletionModel, CompletionRequest, CompletionResponse, ModelChoice,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// One scripted model turn.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MockStep {
+    Message { content: String },
+    ToolCall { name: String, arguments: Value },
+}
+
+#[cfg(test)]
+impl MockStep {
+    pub fn message(content: &str) -> Self {
+        MockStep::Message {
+            content: content.to_string(),
+        }
+    }
+
+    pub fn tool_call(name: &str, arguments: Value) -> Self {
+        MockStep::ToolCall {
+            name: name.to_string(),
+            arguments,
+        }
+    }
+}
+
+/// Completion model that replays a fixed script instead of calling a provider,
+/// for deterministic offline runs of the agent and its tools.
+#[derive(Clone, Default)]
+pub struct MockModel {
+    script: Arc<Mutex<VecDeque<MockStep>>>,
+    prompts: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockModel {
+    pub fn new(script: Vec<MockStep>) -> Self {
+        MockModel {
+            script: Arc::new(Mutex::new(script.into())),
+            prompts: Arc::default(),
+        }
+    }
+
+    /// Load a script: a JSON array of steps such as
+    /// `{"type": "tool_call", "name": "list_chains", "arguments": {}}`.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("read mock script {}", path.display()))?;
+        let script: Vec<MockStep> = serde_json::from_str(&content)
+            .with_context(|| format!("parse mock script {}", path.display()))?;
+        Ok(MockModel::new(script))
+    }
+
+    /// Prompts received so far, in order.
+    #[cfg(test)]
+    pub fn prompts(&self) -> Vec<String> {
+        self.prompts.lock().unwrap().clone()
+    }
+}
+
+impl CompletionModel for MockModel {
+    type Response = ();
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<()>, CompletionError> {
+        self.prompts.lock().unwrap().push(request.prompt);
+        let step = self.script.lock().unwrap().pop_front().ok_or_else(|| {
+            CompletionError::ProviderError("mock script is exhausted".to_string())
+        })?;
+
+        let choice = match step {
+            MockStep::Message { content } => ModelChoice::Message(content),
+            MockStep::ToolCall { name, arguments } => {
+                if !request.tools.iter().any(|tool| tool.name == name) {
+                    return Err(CompletionError::ProviderError(format!(
+                        "mock script calls tool '{}' which the agent does not have",
+                        name
+                    )));
+                }
+                ModelChoice::ToolCall(name, arguments)
+            }
+        };
+        Ok(CompletionResponse {
+            choice,
+            raw_response: (),
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_mock_agent_calls_tool() {
+    use crate::{agent::EthAgent, llm::AgentSpec};
+    use rig::{agent::AgentBuilder, completion::Prompt};
+    use serde_json::json;
+
+    let model = MockModel::new(vec![
+        MockStep::tool_call("list_tokens", json!({"chain": "base", "symbol": "USDC"})),
+        MockStep::message("USDC on base is 0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913."),
+        MockStep::tool_call("bridge", json!({})),
+    ]);
+    let agent = EthAgent.build(AgentBuilder::new(model.clone()));
+
+    let output = agent
+        .prompt("What is the USDC address on base?")
+        .await
+        .unwrap();
+    assert!(output.contains("0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913"));
+    let output = agent.prompt("Thanks").await.unwrap();
+    assert!(output.starts_with("USDC on base"));
+    assert!(agent.prompt("Bridge it").await.is_err());
+    assert!(agent.prompt("Again").await.is_err());
+    assert_eq!(model.prompts().len(), 4);
+}
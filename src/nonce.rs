@@ -0,0 +1,216 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use alloy::primitives::Address;
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::settings::nonces_path;
+
+/// The nonce manager every transaction from this process goes through.
+pub static NONCES: Lazy<NonceManager> = Lazy::new(|| NonceManager::load(nonces_path()));
+
+/// Nonces of one account on one chain.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct AccountNonces {
+    /// Next nonce to hand out once `free` is used up.
+    next: u64,
+    /// Handed out and not known to be mined: sent and waiting, or being sent.
+    pending: BTreeSet<u64>,
+    /// Handed out below `next` but never sent, to be reused before `next`.
+    free: BTreeSet<u64>,
+}
+
+/// What the node reports for an account.
+#[derive(Debug, Clone, Copy)]
+pub struct OnChainNonces {
+    /// Transaction count at the latest block: every nonce below it is mined.
+    pub mined: u64,
+    /// Transaction count including the node's mempool.
+    pub pending: u64,
+}
+
+/// Hands out nonces locally so concurrent sends from one account never collide,
+/// and keeps the ones in flight on disk so a restart continues after them.
+pub struct NonceManager {
+    path: PathBuf,
+    accounts: Mutex<BTreeMap<String, AccountNonces>>,
+}
+
+impl NonceManager {
+    pub fn load(path: &Path) -> Self {
+        let accounts = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("ignoring nonces {}: {}", path.display(), e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        NonceManager {
+            path: path.to_path_buf(),
+            accounts: Mutex::new(accounts),
+        }
+    }
+
+    fn save(&self, accounts: &BTreeMap<String, AccountNonces>) {
+        let saved = (|| -> Result<()> {
+            if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&self.path, serde_json::to_string_pretty(accounts)?)
+                .with_context(|| format!("write nonces {}", self.path.display()))
+        })();
+        if let Err(e) = saved {
+            tracing::error!("failed to save nonces: {:#}", e);
+        }
+    }
+
+    fn update<T>(
+        &self,
+        chain: &str,
+        account: Address,
+        f: impl FnOnce(&mut AccountNonces) -> T,
+    ) -> T {
+        let mut accounts = self.accounts.lock().unwrap();
+        let key = format!("{}:{}", chain, account);
+        let result = f(accounts.entry(key).or_default());
+        self.save(&accounts);
+        result
+    }
+
+    /// The nonce for the next transaction. Local state is trusted while some of
+    /// its nonces are in flight, otherwise the node's count is. Nonces in flight
+    /// above a gap the node does not know about are dropped.
+    pub fn allocate(&self, chain: &str, account: Address, on_chain: OnChainNonces) -> u64 {
+        self.update(chain, account, |nonces| {
+            nonces.pending.retain(|n| *n >= on_chain.mined);
+            nonces.free.retain(|n| *n >= on_chain.mined);
+            // Nothing fills the nonces between the node's count and ours, so the
+            // transactions using them were dropped and would never be mined.
+            if let Some(lowest) = nonces.pending.first().filter(|n| **n > on_chain.pending) {
+                warn!(chain, %account, lowest, on_chain = on_chain.pending, "nonce gap, reallocating");
+                nonces.pending.clear();
+            }
+            nonces.next = if nonces.pending.is_empty() {
+                nonces.free.clear();
+                on_chain.pending.max(on_chain.mined)
+            } else {
+                nonces.next.max(on_chain.pending)
+            };
+            let nonce = nonces.free.pop_first().unwrap_or_else(|| {
+                nonces.next += 1;
+                nonces.next - 1
+            });
+            nonces.pending.insert(nonce);
+            nonce
+        })
+    }
+
+    /// The transaction with `nonce` was mined.
+    pub fn confirm(&self, chain: &str, account: Address, nonce: u64) {
+        self.update(chain, account, |nonces| {
+            nonces.pending.remove(&nonce);
+        })
+    }
+
+    /// The transaction with `nonce` was not sent, so the nonce can be used again.
+    pub fn release(&self, chain: &str, account: Address, nonce: u64) {
+        self.update(chain, account, |nonces| {
+            if nonces.pending.remove(&nonce) {
+                if nonce + 1 == nonces.next {
+                    nonces.next = nonce;
+                } else {
+                    nonces.free.insert(nonce);
+                }
+            }
+        })
+    }
+
+    /// Forget local state, e.g. after the node rejected a nonce as too low; the
+    /// next allocation starts from the node's count.
+    pub fn resync(&self, chain: &str, account: Address) {
+        info!(chain, %account, "resyncing nonces");
+        self.update(chain, account, |nonces| *nonces = AccountNonces::default())
+    }
+}
+
+/// Whether a send failed because the nonce was already used.
+pub fn is_nonce_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    [
+        "nonce too low",
+        "nonce is too low",
+        "nonce_expired",
+        "invalid nonce",
+    ]
+    .iter()
+    .any(|pattern| error.contains(pattern))
+}
+
+#[test]
+fn test_nonce_manager() {
+    let path = std::env::temp_dir().join(format!("rig-eth-nonces-{}.json", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let account = Address::repeat_byte(0x33);
+    let at = |mined, pending| OnChainNonces { mined, pending };
+
+    let nonces = NonceManager::load(&path);
+    // Concurrent sends get consecutive nonces even though the node still reports 5.
+    assert_eq!(nonces.allocate("base", account, at(5, 5)), 5);
+    assert_eq!(nonces.allocate("base", account, at(5, 5)), 6);
+    assert_eq!(nonces.allocate("base", account, at(5, 5)), 7);
+    assert_eq!(nonces.allocate("arbitrum", account, at(0, 0)), 0);
+
+    // 6 was never sent: it is handed out again before 8.
+    nonces.release("base", account, 6);
+    assert_eq!(nonces.allocate("base", account, at(5, 6)), 6);
+    nonces.release("base", account, 7);
+    assert_eq!(nonces.allocate("base", account, at(5, 6)), 7);
+
+    // A restart continues after the transactions still in flight.
+    let restarted = NonceManager::load(&path);
+    assert_eq!(restarted.allocate("base", account, at(5, 5)), 8);
+
+    // Once everything is mined, the node's count wins again.
+    for n in 5..=8 {
+        restarted.confirm("base", account, n);
+    }
+    assert_eq!(restarted.allocate("base", account, at(12, 12)), 12);
+    restarted.resync("base", account);
+    assert_eq!(restarted.allocate("base", account, at(13, 14)), 14);
+
+    assert!(is_nonce_error(
+        "server returned an error response: nonce too low"
+    ));
+    assert!(!is_nonce_error(
+        "insufficient funds for gas * price + value"
+    ));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_nonce_gap() {
+    let path = std::env::temp_dir().join(format!("rig-eth-gap-{}.json", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let account = Address::repeat_byte(0x34);
+    let at = |mined, pending| OnChainNonces { mined, pending };
+
+    let nonces = NonceManager::load(&path);
+    assert_eq!(nonces.allocate("base", account, at(5, 5)), 5);
+    assert_eq!(nonces.allocate("base", account, at(5, 5)), 6);
+    assert_eq!(nonces.allocate("base", account, at(5, 7)), 7);
+    // 5 was mined, then reorged out and dropped with 6 and 7: the node is back
+    // at 5, and 8 would wait behind the gap forever.
+    nonces.confirm("base", account, 5);
+    assert_eq!(nonces.allocate("base", account, at(5, 5)), 5);
+    // Being sent but not yet seen by the node is no gap.
+    assert_eq!(nonces.allocate("base", account, at(5, 5)), 6);
+    assert_eq!(nonces.allocate("base", account, at(5, 6)), 7);
+    fs::remove_file(&path).unwrap();
+}
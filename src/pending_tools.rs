@@ -0,0 +1,459 @@
+use std::str::FromStr;
+
+use alloy::{
+    primitives::{utils::format_ether, Address, B256, U256},
+    rpc::types::TransactionRequest,
+};
+use rig::{completion::ToolDefinition, tool::Tool};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+
+use crate::{
+    chains::{get_chain_info, ChainInfo},
+    fee::{estimate_cost, trim_decimal, FeeTier},
+    proposal::propose,
+    settings::{approval_required, dry_run, stuck_after},
+    tool_schema::{chain_schema, parameters, tx_hash_schema},
+    tx_output::TxOutput,
+    tx_tracker::{SentTx, TX_TRACKER},
+    wallet::send_transaction,
+};
+
+#[derive(Debug, thiserror::Error)]
+#[error("Pending transaction error: {message}")]
+pub struct PendingTxError {
+    message: String,
+}
+
+impl PendingTxError {
+    fn new(e: impl std::fmt::Display) -> Self {
+        PendingTxError {
+            message: format!("{:#}", e),
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ListPendingTxsArgs {
+    /// Optional chain name, alias or chain ID to list. Omit to list every chain
+    #[serde(default)]
+    chain: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ListPendingTxs;
+impl Tool for ListPendingTxs {
+    const NAME: &'static str = "list_pending_txs";
+
+    type Error = PendingTxError;
+    type Args = ListPendingTxsArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "list_pending_txs".to_string(),
+            description: "List transactions sent by this agent that are not mined yet, \
+                          and which of them are stuck. Transactions on chains that could \
+                          not be queried are listed as unchecked"
+                .to_string(),
+            parameters: parameters::<ListPendingTxsArgs>(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let chain = match &args.chain {
+            Some(chain) => Some(get_chain_info(chain).map_err(PendingTxError::new)?.chain),
+            None => None,
+        };
+        let txs = TX_TRACKER.pending_txs(chain.as_deref()).await;
+        let pending: Vec<_> = txs
+            .pending
+            .into_iter()
+            .map(|tx| {
+                json!({
+                    "chain": tx.chain,
+                    "tx_hash": tx.hash,
+                    "nonce": tx.tx.nonce,
+                    "summary": tx.summary,
+                    "pending_secs": tx.sent_at.elapsed().unwrap_or_default().as_secs(),
+                    "stuck": tx.is_stuck(),
+                })
+            })
+            .collect();
+        let unchecked: Vec<_> = txs
+            .unchecked
+            .into_iter()
+            .map(|(tx, error)| {
+                json!({
+                    "chain": tx.chain,
+                    "tx_hash": tx.hash,
+                    "summary": tx.summary,
+                    "error": error,
+                })
+            })
+            .collect();
+        Ok(json!({
+            "pending": pending,
+            "unchecked": unchecked,
+            "stuck_after_secs": stuck_after().as_secs(),
+        })
+        .to_string())
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ReplaceTxArgs {
+    /// The chain name, alias or chain ID the transaction was sent on
+    #[schemars(schema_with = "chain_schema")]
+    chain: String,
+    /// Hash of the pending transaction, from list_pending_txs
+    #[schemars(schema_with = "tx_hash_schema")]
+    tx_hash: String,
+    /// How fast the replacement should be included. It always pays more than the original
+    #[serde(default = "fast")]
+    speed: FeeTier,
+}
+
+fn fast() -> FeeTier {
+    FeeTier::Fast
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SpeedUpTx;
+impl Tool for SpeedUpTx {
+    const NAME: &'static str = "speed_up_tx";
+
+    type Error = PendingTxError;
+    type Args = ReplaceTxArgs;
+    type Output = TxOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "speed_up_tx".to_string(),
+            description: "Resend a pending transaction with the same nonce and a higher fee \
+                          so it is mined sooner"
+                .to_string(),
+            parameters: parameters::<ReplaceTxArgs>(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let (chain_info, sent) = pending_tx(&args).await?;
+        let to = recipient(&sent.tx)?;
+        let output = TxOutput::new(
+            Self::NAME,
+            format!("speed up {}", sent.summary),
+            &chain_info,
+            to,
+            None,
+            trim_decimal(&format_ether(sent.tx.value.unwrap_or_default())),
+        );
+        replace(
+            Delivery::current(),
+            output,
+            &chain_info,
+            sent.tx,
+            args.speed,
+        )
+        .await
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CancelTx;
+impl Tool for CancelTx {
+    const NAME: &'static str = "cancel_tx";
+
+    type Error = PendingTxError;
+    type Args = ReplaceTxArgs;
+    type Output = TxOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "cancel_tx".to_string(),
+            description: "Cancel a pending transaction by replacing it with a zero-value \
+                          transfer to yourself with the same nonce and a higher fee"
+                .to_string(),
+            parameters: parameters::<ReplaceTxArgs>(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let (chain_info, sent) = pending_tx(&args).await?;
+        let tx = cancel_tx(&sent.tx);
+        let to = tx.from.unwrap_or_default();
+        let output = TxOutput::new(
+            Self::NAME,
+            format!("cancel {}", sent.summary),
+            &chain_info,
+            to,
+            None,
+            "0".to_string(),
+        );
+        replace(Delivery::current(), output, &chain_info, tx, args.speed).await
+    }
+}
+
+async fn pending_tx(args: &ReplaceTxArgs) -> Result<(ChainInfo, SentTx), PendingTxError> {
+    let chain_info = get_chain_info(&args.chain).map_err(PendingTxError::new)?;
+    let hash = B256::from_str(&args.tx_hash).map_err(PendingTxError::new)?;
+    let sent = TX_TRACKER
+        .find_pending(&chain_info.chain, hash)
+        .await
+        .map_err(PendingTxError::new)?;
+    info!(chain = chain_info.chain, %hash, nonce = sent.tx.nonce, "replacing transaction");
+    Ok((chain_info, sent))
+}
+
+// Where a speed-up sends to, the same address as the original. A contract
+// deployment has none.
+fn recipient(tx: &TransactionRequest) -> Result<Address, PendingTxError> {
+    tx.to
+        .and_then(|to| to.to().copied())
+        .ok_or_else(|| PendingTxError {
+            message: "cannot speed up a contract deployment, cancel it instead".to_string(),
+        })
+}
+
+/// A zero-value transfer to the sender, taking the place of `pending`. It keeps
+/// the fees of `pending` so the replacement is priced above them.
+fn cancel_tx(pending: &TransactionRequest) -> TransactionRequest {
+    let from = pending.from.unwrap_or_default();
+    TransactionRequest {
+        from: Some(from),
+        to: Some(from.into()),
+        value: Some(U256::ZERO),
+        nonce: pending.nonce,
+        max_fee_per_gas: pending.max_fee_per_gas,
+        max_priority_fee_per_gas: pending.max_priority_fee_per_gas,
+        gas_price: pending.gas_price,
+        ..Default::default()
+    }
+}
+
+/// What `replace` does with the replacement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Delivery {
+    DryRun,
+    Propose,
+    Send,
+}
+
+impl Delivery {
+    /// From the dry-run and approval settings.
+    fn current() -> Self {
+        if dry_run() {
+            Delivery::DryRun
+        } else if approval_required() {
+            Delivery::Propose
+        } else {
+            Delivery::Send
+        }
+    }
+}
+
+// Deliver `tx`, which keeps the nonce of the transaction it replaces.
+async fn replace(
+    delivery: Delivery,
+    output: TxOutput,
+    chain_info: &ChainInfo,
+    tx: TransactionRequest,
+    speed: FeeTier,
+) -> Result<TxOutput, PendingTxError> {
+    let output = output.with_gas_estimate(estimate_cost(chain_info, &tx, speed).await);
+    match delivery {
+        Delivery::DryRun => return Ok(output),
+        Delivery::Propose => {
+            return propose(output, tx, speed).map_err(|e| PendingTxError {
+                message: format!("propose error: {:#}", e),
+            })
+        }
+        Delivery::Send => {}
+    }
+    let summary = output.summary.clone();
+    let broadcast = send_transaction(chain_info, tx, speed)
+        .await
+        .map_err(|e| PendingTxError {
+            message: format!("replace error: {}", e),
+        })?;
+    TX_TRACKER.record_sent(&chain_info.chain, &broadcast, summary);
+    Ok(output.with_broadcast(chain_info, broadcast))
+}
+
+#[test]
+fn test_cancel_tx() {
+    use alloy::primitives::Address;
+
+    let from = Address::repeat_byte(0x55);
+    let pending = TransactionRequest::default()
+        .from(from)
+        .to(Address::repeat_byte(0x66))
+        .value(U256::from(10u64.pow(18)))
+        .nonce(3)
+        .max_fee_per_gas(40)
+        .max_priority_fee_per_gas(2);
+    let cancel = cancel_tx(&pending);
+    assert_eq!(cancel.to, Some(from.into()));
+    assert_eq!(cancel.value, Some(U256::ZERO));
+    assert_eq!(cancel.nonce, Some(3));
+    assert_eq!(cancel.max_fee_per_gas, Some(40));
+    assert_eq!(cancel.max_priority_fee_per_gas, Some(2));
+
+    assert_eq!(recipient(&pending).unwrap(), Address::repeat_byte(0x66));
+    let deployment = TransactionRequest {
+        to: Some(alloy::primitives::TxKind::Create),
+        ..pending
+    };
+    assert!(recipient(&deployment)
+        .unwrap_err()
+        .message
+        .starts_with("cannot speed up a contract deployment"));
+}
+
+#[tokio::test]
+async fn test_replace() {
+    use alloy::primitives::Address;
+
+    use crate::{proposal::ProposalStore, tx_output::TxStatus};
+
+    // Nothing listens there, so the estimate fails fast and nothing is sent.
+    let mut chain_info = get_chain_info("base").unwrap();
+    chain_info.provider_url = "http://127.0.0.1:1".to_string();
+    let pending = TransactionRequest::default()
+        .from(Address::repeat_byte(0x56))
+        .to(Address::repeat_byte(0x66))
+        .nonce(4)
+        .max_fee_per_gas(40)
+        .max_priority_fee_per_gas(2);
+    let output = || {
+        TxOutput::new(
+            SpeedUpTx::NAME,
+            "speed up transfer".to_string(),
+            &chain_info,
+            Address::repeat_byte(0x66),
+            None,
+            "0".to_string(),
+        )
+    };
+
+    let dry = replace(
+        Delivery::DryRun,
+        output(),
+        &chain_info,
+        pending.clone(),
+        FeeTier::Fast,
+    )
+    .await
+    .unwrap();
+    assert_eq!(dry.status, TxStatus::DryRun);
+    assert_eq!(dry.tx_hash, None);
+
+    // The proposal keeps the nonce and fees, so approving it bumps them then.
+    let proposed = replace(
+        Delivery::Propose,
+        output(),
+        &chain_info,
+        pending.clone(),
+        FeeTier::Fast,
+    )
+    .await
+    .unwrap();
+    assert_eq!(proposed.status, TxStatus::Proposed);
+    let proposal = ProposalStore::default()
+        .list(None)
+        .unwrap()
+        .into_iter()
+        .find(|proposal| Some(&proposal.id) == proposed.proposal_id.as_ref())
+        .unwrap();
+    assert_eq!(proposal.tx.nonce, Some(4));
+    assert_eq!(proposal.tx.max_fee_per_gas, Some(40));
+
+    let error = replace(
+        Delivery::Send,
+        output(),
+        &chain_info,
+        pending,
+        FeeTier::Fast,
+    )
+    .await
+    .unwrap_err();
+    assert!(error.message.starts_with("replace error"), "{}", error);
+}
+
+// A replacement outbids the pending transaction enough for the node to take it.
+#[tokio::test]
+async fn test_replace_bumps_fees() -> anyhow::Result<()> {
+    use alloy::{
+        consensus::Transaction,
+        network::{EthereumWallet, TransactionBuilder},
+        providers::{Provider, ProviderBuilder},
+    };
+    use serde_json::Value;
+
+    use crate::{anvil_harness::AnvilHarness, tx_output::TxStatus, wallet::local_signer};
+
+    let anvil = AnvilHarness::spawn().await?;
+    let chain_info = get_chain_info(&anvil.chain)?;
+    let signer = local_signer();
+    let from = signer.address();
+    let provider = ProviderBuilder::new()
+        .with_recommended_fillers()
+        .wallet(EthereumWallet::from(signer))
+        .on_http(anvil.anvil.endpoint_url());
+    // Blocks are only mined on request from here, so the original stays pending.
+    provider
+        .raw_request::<_, Value>("evm_setAutomine".into(), (false,))
+        .await?;
+
+    let gwei = 1_000_000_000u128;
+    let to = Address::repeat_byte(0x78);
+    let original = TransactionRequest::default()
+        .with_from(from)
+        .with_to(to)
+        .with_value(U256::from(1000))
+        .with_nonce(provider.get_transaction_count(from).await?)
+        .with_gas_limit(21_000)
+        .with_max_fee_per_gas(3 * gwei)
+        .with_max_priority_fee_per_gas(gwei);
+    let original_hash = *provider.send_transaction(original.clone()).await?.tx_hash();
+
+    let output = TxOutput::new(
+        SpeedUpTx::NAME,
+        "speed up transfer".to_string(),
+        &chain_info,
+        to,
+        None,
+        "0.000000000000001".to_string(),
+    );
+    let replacing = tokio::spawn(async move {
+        replace(Delivery::Send, output, &chain_info, original, FeeTier::Slow).await
+    });
+    // The node drops the original once it takes the replacement.
+    for _ in 0..50 {
+        if replacing.is_finished()
+            || provider
+                .get_transaction_by_hash(original_hash)
+                .await?
+                .is_none()
+        {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    provider
+        .raw_request::<_, Value>("evm_mine".into(), ())
+        .await?;
+
+    let replaced = replacing.await?.map_err(|e| anyhow::anyhow!(e))?;
+    assert_eq!(replaced.status, TxStatus::Success);
+    let hash = replaced.tx_hash.unwrap();
+    assert_ne!(hash, original_hash);
+    let mined = provider.get_transaction_by_hash(hash).await?.unwrap();
+    assert!(mined.max_fee_per_gas() >= 3 * gwei * 115 / 100);
+    assert!(mined.max_priority_fee_per_gas() >= Some(gwei * 115 / 100));
+    Ok(())
+}
@@ -0,0 +1,462 @@
+use std::{
+    fmt, fs,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alloy::{hex, primitives::B256, rpc::types::TransactionRequest};
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    audit::AuditRecord,
+    chains::get_chain_info,
+    fee::FeeTier,
+    settings::{proposal_ttl, proposals_path},
+    tx_output::{TxOutput, TxStatus},
+    tx_tracker::TX_TRACKER,
+    users::{self, User, Users},
+    wallet::send_transaction,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalStatus {
+    Pending,
+    /// Approved and sent.
+    Approved,
+    Rejected,
+    Expired,
+    /// Approved, but sending failed.
+    Failed,
+}
+
+impl fmt::Display for ProposalStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProposalStatus::Pending => "pending",
+            ProposalStatus::Approved => "approved",
+            ProposalStatus::Rejected => "rejected",
+            ProposalStatus::Expired => "expired",
+            ProposalStatus::Failed => "failed",
+        })
+    }
+}
+
+/// A transaction a tool prepared but did not sign.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Proposal {
+    pub id: String,
+    /// User of the HTTP API who proposed it, the only one who can see and approve it.
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Bot chat it was proposed in, the only chat that can see and approve it.
+    #[serde(default)]
+    pub chat: Option<String>,
+    pub status: ProposalStatus,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// What the tool returned, with the human summary.
+    pub output: TxOutput,
+    /// Unsigned transaction; nonce, gas and fees are filled in when it is sent.
+    pub tx: TransactionRequest,
+    /// Speed the fees are estimated for at approval.
+    #[serde(default)]
+    pub speed: FeeTier,
+    pub tx_hash: Option<B256>,
+    pub error: Option<String>,
+}
+
+impl Proposal {
+    // Users and chats only see their own proposals; the CLI sees all of them.
+    fn visible(&self) -> bool {
+        users::current().is_none_or(|user| self.user.as_ref() == Some(&user.name))
+            && current_chat().is_none_or(|chat| self.chat.as_ref() == Some(&chat))
+    }
+}
+
+tokio::task_local! {
+    static CHAT: String;
+}
+
+/// Run `f` for a bot chat: proposals made in it belong to the chat, and only
+/// the chat's own proposals can be listed, approved or rejected.
+pub async fn in_chat<F: Future>(chat: &str, f: F) -> F::Output {
+    CHAT.scope(chat.to_string(), f).await
+}
+
+fn current_chat() -> Option<String> {
+    CHAT.try_with(String::clone).ok()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProposalError {
+    #[error("no proposal {0}")]
+    NotFound(String),
+    #[error("proposal {id} is {status}")]
+    NotPending { id: String, status: ProposalStatus },
+    /// The proposer can't be looked up, and sending as anyone else would skip their limits.
+    #[error("proposal {id} was made by unknown user {user}")]
+    UnknownProposer { id: String, user: String },
+    /// Signing or sending the approved transaction failed.
+    #[error(transparent)]
+    Send(anyhow::Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+type Result<T, E = ProposalError> = std::result::Result<T, E>;
+
+// Serializes read-modify-write cycles on the store file within this process.
+static STORE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Proposals kept in a JSON file, so a later process (or the HTTP API) can approve them.
+pub struct ProposalStore {
+    path: PathBuf,
+    /// Where proposers are looked up when approving outside of their scope (CLI).
+    users: Option<Users>,
+}
+
+impl Default for ProposalStore {
+    fn default() -> Self {
+        ProposalStore::new(proposals_path())
+    }
+}
+
+impl ProposalStore {
+    pub fn new(path: &Path) -> Self {
+        ProposalStore {
+            path: path.to_path_buf(),
+            users: None,
+        }
+    }
+
+    pub fn with_users(mut self, users: Users) -> Self {
+        self.users = Some(users);
+        self
+    }
+
+    fn load(&self) -> anyhow::Result<Vec<Proposal>> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("parse proposals {}", self.path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e).with_context(|| format!("read proposals {}", self.path.display())),
+        }
+    }
+
+    fn save(&self, proposals: &[Proposal]) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(proposals)?)
+            .with_context(|| format!("write proposals {}", self.path.display()))
+    }
+
+    /// Load, expire stale proposals, apply `f` and write back.
+    fn update<T>(&self, f: impl FnOnce(&mut Vec<Proposal>) -> Result<T>) -> Result<T> {
+        let _guard = STORE_LOCK.lock().unwrap();
+        let mut proposals = self.load()?;
+        let now = Utc::now();
+        for p in proposals.iter_mut() {
+            if p.status == ProposalStatus::Pending && p.expires_at <= now {
+                p.status = ProposalStatus::Expired;
+            }
+        }
+        let result = f(&mut proposals);
+        self.save(&proposals)?;
+        result
+    }
+
+    fn update_one<T>(&self, id: &str, f: impl FnOnce(&mut Proposal) -> Result<T>) -> Result<T> {
+        self.update(|proposals| {
+            let proposal = proposals
+                .iter_mut()
+                .find(|p| p.id == id && p.visible())
+                .ok_or_else(|| ProposalError::NotFound(id.to_string()))?;
+            f(proposal)
+        })
+    }
+
+    /// Store `tx` for approval within `ttl`. The output is marked as proposed.
+    pub fn propose(
+        &self,
+        output: TxOutput,
+        tx: TransactionRequest,
+        speed: FeeTier,
+        ttl: Duration,
+    ) -> Result<Proposal> {
+        let id = hex::encode(rand::random::<[u8; 8]>());
+        let created_at = Utc::now();
+        let ttl = chrono::Duration::from_std(ttl).map_err(|e| anyhow!("invalid ttl: {}", e))?;
+        let proposal = Proposal {
+            id: id.clone(),
+            user: users::current().map(|user| user.name.clone()),
+            chat: current_chat(),
+            status: ProposalStatus::Pending,
+            created_at,
+            expires_at: created_at + ttl,
+            output: TxOutput {
+                status: TxStatus::Proposed,
+                proposal_id: Some(id),
+                ..output
+            },
+            tx,
+            speed,
+            tx_hash: None,
+            error: None,
+        };
+        self.update(|proposals| {
+            proposals.push(proposal.clone());
+            Ok(())
+        })?;
+        Ok(proposal)
+    }
+
+    /// Proposals of the current user with the given status, all of them when `None`,
+    /// oldest first. Without a user, everyone's.
+    pub fn list(&self, status: Option<ProposalStatus>) -> Result<Vec<Proposal>> {
+        self.update(|proposals| {
+            Ok(proposals
+                .iter()
+                .filter(|p| p.visible() && status.is_none_or(|s| p.status == s))
+                .cloned()
+                .collect())
+        })
+    }
+
+    fn take_pending(&self, id: &str, status: ProposalStatus) -> Result<Proposal> {
+        self.update_one(id, |p| match p.status {
+            ProposalStatus::Pending => {
+                p.status = status;
+                Ok(p.clone())
+            }
+            status => Err(ProposalError::NotPending {
+                id: id.to_string(),
+                status,
+            }),
+        })
+    }
+
+    pub fn reject(&self, id: &str) -> Result<Proposal> {
+        let record = AuditRecord::new("reject_proposal", json!({ "id": id }));
+        let result = self.take_pending(id, ProposalStatus::Rejected);
+        match &result {
+            Ok(p) => record.with_output(&json!({ "chain": p.output.chain })),
+            Err(e) => record.rejected(e.to_string()),
+        }
+        .finish();
+        result
+    }
+
+    /// The user a proposal is sent as: whoever proposed it, with their account and
+    /// limits. `None` for proposals made outside of a user scope.
+    fn proposer(&self, proposal: &Proposal) -> Result<Option<Arc<User>>> {
+        let Some(name) = &proposal.user else {
+            return Ok(None);
+        };
+        users::current()
+            .filter(|user| &user.name == name)
+            .or_else(|| self.users.as_ref()?.get(name))
+            .map(Some)
+            .ok_or_else(|| ProposalError::UnknownProposer {
+                id: proposal.id.clone(),
+                user: name.clone(),
+            })
+    }
+
+    /// Sign and send a pending proposal on behalf of its proposer. It is marked
+    /// approved before sending, so it can never be sent twice.
+    pub async fn approve(&self, id: &str) -> Result<TxOutput> {
+        let record = AuditRecord::new("approve_proposal", json!({ "id": id }));
+        let taken = self
+            .update_one(id, |p| Ok(p.clone()))
+            .and_then(|p| self.proposer(&p))
+            .and_then(|user| Ok((user, self.take_pending(id, ProposalStatus::Approved)?)));
+        let (user, proposal) = match taken {
+            Ok(taken) => taken,
+            Err(e) => {
+                record.rejected(e.to_string()).finish();
+                return Err(e);
+            }
+        };
+
+        let sent = match user {
+            Some(user) => users::scope(user, self.send(proposal)).await,
+            None => self.send(proposal).await,
+        };
+        match sent {
+            Ok(output) => {
+                self.update_one(id, |p| {
+                    p.tx_hash = output.tx_hash;
+                    Ok(())
+                })?;
+                record
+                    .with_output(&serde_json::to_value(&output).unwrap_or_default())
+                    .finish();
+                Ok(output)
+            }
+            Err(e) => {
+                let message = format!("{:#}", e);
+                self.update_one(id, |p| {
+                    p.status = ProposalStatus::Failed;
+                    p.error = Some(message.clone());
+                    Ok(())
+                })?;
+                record.failed(message).finish();
+                Err(ProposalError::Send(e))
+            }
+        }
+    }
+
+    async fn send(&self, proposal: Proposal) -> anyhow::Result<TxOutput> {
+        let chain_info = get_chain_info(&proposal.output.chain)?;
+        let broadcast = send_transaction(&chain_info, proposal.tx, proposal.speed).await?;
+        TX_TRACKER.record_sent(
+            &chain_info.chain,
+            &broadcast,
+            proposal.output.summary.clone(),
+        );
+        Ok(proposal.output.with_broadcast(&chain_info, broadcast))
+    }
+}
+
+/// Store a tool's transaction in the default store with the configured TTL, and
+/// return the tool output to show in its place.
+pub fn propose(
+    output: TxOutput,
+    tx: TransactionRequest,
+    speed: FeeTier,
+) -> anyhow::Result<TxOutput> {
+    let proposal = ProposalStore::default().propose(output, tx, speed, proposal_ttl())?;
+    Ok(proposal.output)
+}
+
+#[tokio::test]
+async fn test_proposal_lifecycle() {
+    use alloy::{network::TransactionBuilder, primitives::Address};
+
+    let path = std::env::temp_dir().join(format!("rig-eth-proposals-{}.json", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let store = ProposalStore::new(&path);
+    let chain_info = get_chain_info("base").unwrap();
+    let to = Address::repeat_byte(0x11);
+    let output = TxOutput::new(
+        "eth_transfer",
+        format!("transfer 1 ETH to {}", to),
+        &chain_info,
+        to,
+        None,
+        "1".to_string(),
+    );
+    let tx = TransactionRequest::default().with_to(to);
+
+    let kept = store
+        .propose(
+            output.clone(),
+            tx.clone(),
+            FeeTier::Fast,
+            Duration::from_secs(600),
+        )
+        .unwrap();
+    assert_eq!(kept.output.status, TxStatus::Proposed);
+    assert_eq!(kept.output.proposal_id.as_ref(), Some(&kept.id));
+    let stale = store
+        .propose(output, tx, FeeTier::Normal, Duration::from_secs(0))
+        .unwrap();
+
+    let pending = store.list(Some(ProposalStatus::Pending)).unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].tx.to, kept.tx.to);
+    assert_eq!(pending[0].speed, FeeTier::Fast);
+    assert_eq!(
+        store.list(Some(ProposalStatus::Expired)).unwrap()[0].id,
+        stale.id
+    );
+
+    assert_eq!(
+        store.reject(&kept.id).unwrap().status,
+        ProposalStatus::Rejected
+    );
+    assert!(matches!(
+        store.reject(&kept.id),
+        Err(ProposalError::NotPending { .. })
+    ));
+    assert!(matches!(
+        store.approve(&stale.id).await,
+        Err(ProposalError::NotPending {
+            status: ProposalStatus::Expired,
+            ..
+        })
+    ));
+    assert!(matches!(
+        store.approve("nope").await,
+        Err(ProposalError::NotFound(_))
+    ));
+    assert_eq!(store.list(None).unwrap().len(), 2);
+    fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_approve_as_proposer() {
+    use alloy::{
+        network::TransactionBuilder,
+        primitives::{utils::parse_ether, Address},
+    };
+
+    let path = std::env::temp_dir().join(format!(
+        "rig-eth-proposals-user-{}.json",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    users::set_test_key();
+    let config = || {
+        serde_json::from_str(
+            r#"{"users": {"alice": {"token": "a-token", "max_tx_value": "1",
+                                    "private_key_env": "RIG_ETH_TEST_KEY"}}}"#,
+        )
+        .unwrap()
+    };
+    let alice = Users::from_config(config())
+        .unwrap()
+        .authenticate("a-token")
+        .unwrap();
+    let chain_info = get_chain_info("base").unwrap();
+    let to = Address::repeat_byte(0x11);
+    let output = TxOutput::new(
+        "eth_transfer",
+        format!("transfer 2 ETH to {}", to),
+        &chain_info,
+        to,
+        None,
+        "2".to_string(),
+    );
+    let tx = TransactionRequest::default()
+        .with_to(to)
+        .with_value(parse_ether("2").unwrap());
+    let store = ProposalStore::new(&path);
+    let proposal = users::scope(alice, async {
+        store.propose(output, tx, FeeTier::Normal, Duration::from_secs(600))
+    })
+    .await
+    .unwrap();
+    assert_eq!(proposal.user.as_deref(), Some("alice"));
+
+    // Without alice's account and limits the approval is refused and stays pending.
+    assert!(matches!(
+        store.approve(&proposal.id).await,
+        Err(ProposalError::UnknownProposer { .. })
+    ));
+    assert_eq!(store.list(Some(ProposalStatus::Pending)).unwrap().len(), 1);
+
+    // As alice, her limit stops it before anything is signed.
+    let store = ProposalStore::new(&path).with_users(Users::from_config(config()).unwrap());
+    let error = store.approve(&proposal.id).await.unwrap_err();
+    assert!(error.to_string().contains("may send at most"), "{error}");
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,140 @@
+use rig::{completion::ToolDefinition, tool::Tool};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::chains::{chain_infos, get_chain_info};
+use crate::tool_schema::{chain_schema, parameters};
+
+#[derive(Debug, thiserror::Error)]
+#[error("Registry error: {message}")]
+pub struct RegistryError {
+    message: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ListChainsArgs {}
+
+#[derive(Deserialize, Serialize)]
+pub struct ListChains;
+impl Tool for ListChains {
+    const NAME: &'static str = "list_chains";
+
+    type Error = RegistryError;
+    type Args = ListChainsArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "list_chains".to_string(),
+            description: "List the configured chains with their chain IDs and aliases".to_string(),
+            parameters: parameters::<ListChainsArgs>(),
+        }
+    }
+
+    async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let chains: Vec<_> = chain_infos()
+            .iter()
+            .map(|info| {
+                json!({
+                    "chain": info.chain,
+                    "chain_id": info.chain_id,
+                    "aliases": info.aliases,
+                })
+            })
+            .collect();
+        Ok(json!(chains).to_string())
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ListTokensArgs {
+    /// The chain name, alias or chain ID, such as arbitrum
+    #[schemars(schema_with = "chain_schema")]
+    chain: String,
+    /// Optional token symbol to look up, such as USDC. Omit to list all tokens
+    #[serde(default)]
+    symbol: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ListTokens;
+impl Tool for ListTokens {
+    const NAME: &'static str = "list_tokens";
+
+    type Error = RegistryError;
+    type Args = ListTokensArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "list_tokens".to_string(),
+            description: "Look up the ERC20 token addresses known on a chain".to_string(),
+            parameters: parameters::<ListTokensArgs>(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let chain_info = get_chain_info(&args.chain).map_err(|e| RegistryError {
+            message: e.to_string(),
+        })?;
+
+        let mut tokens: Vec<_> = chain_info
+            .tokens
+            .iter()
+            .filter(|(symbol, _)| match &args.symbol {
+                Some(wanted) => symbol.eq_ignore_ascii_case(wanted.trim()),
+                None => true,
+            })
+            .collect();
+        if tokens.is_empty() {
+            return Err(RegistryError {
+                message: format!(
+                    "no token {} on {}",
+                    args.symbol.unwrap_or_default(),
+                    chain_info.chain
+                ),
+            });
+        }
+        tokens.sort();
+
+        let tokens: Vec<_> = tokens
+            .into_iter()
+            .map(|(symbol, address)| {
+                let mut token = json!({ "symbol": symbol, "address": address });
+                if let Some(meta) = chain_info.token_metadata.get(symbol) {
+                    token["decimals"] = json!(meta.decimals);
+                }
+                token
+            })
+            .collect();
+        Ok(json!({ "chain": chain_info.chain, "tokens": tokens }).to_string())
+    }
+}
+
+#[tokio::test]
+async fn test_list_tokens() {
+    let output = ListTokens
+        .call(ListTokensArgs {
+            chain: "Base".to_string(),
+            symbol: Some("usdc".to_string()),
+        })
+        .await
+        .unwrap();
+    let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(output["chain"], "base");
+    assert_eq!(
+        output["tokens"],
+        json!([{ "symbol": "USDC", "address": "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913" }])
+    );
+
+    assert!(ListTokens
+        .call(ListTokensArgs {
+            chain: "base".to_string(),
+            symbol: Some("NOPE".to_string()),
+        })
+        .await
+        .is_err());
+}
@@ -0,0 +1,167 @@
+use std::io::Write;
+
+use anyhow::Result;
+use rig::completion::{Chat, Message};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::{info_span, Instrument};
+
+use crate::{
+    audit,
+    chains::get_chain_info,
+    tx_output::render,
+    tx_tracker::{TxTracker, TX_TRACKER},
+};
+
+const HELP: &str = "commands:
+  /chain [name]  show or switch the default chain
+  /pending       list sent transactions without a receipt, and which are stuck
+  /clear         forget the conversation history
+  /exit          leave the session";
+
+/// Multi-turn chat state: history is replayed to the agent on every turn.
+pub struct ReplSession {
+    history: Vec<Message>,
+    chain: Option<String>,
+    /// Where `/pending` looks, the process's tracker outside of tests.
+    tracker: &'static TxTracker,
+}
+
+impl Default for ReplSession {
+    fn default() -> Self {
+        ReplSession {
+            history: vec![],
+            chain: None,
+            tracker: &TX_TRACKER,
+        }
+    }
+}
+
+impl ReplSession {
+    /// Run a `/command`. Returns `None` when the session should end.
+    async fn command(&mut self, line: &str) -> Option<String> {
+        let mut parts = line.split_whitespace();
+        let reply = match (parts.next().unwrap_or_default(), parts.next()) {
+            ("/exit" | "/quit", _) => return None,
+            ("/clear", _) => {
+                self.history.clear();
+                "history cleared".to_string()
+            }
+            ("/chain", None) => match &self.chain {
+                Some(chain) => format!("default chain: {}", chain),
+                None => "no default chain".to_string(),
+            },
+            ("/chain", Some(name)) => match get_chain_info(name) {
+                Ok(info) => {
+                    self.chain = Some(info.chain.clone());
+                    format!("default chain: {}", info.chain)
+                }
+                Err(e) => e.to_string(),
+            },
+            ("/pending", _) => {
+                let txs = self.tracker.pending_txs(None).await;
+                let pending = txs.pending.iter().map(|tx| {
+                    let age = tx.sent_at.elapsed().unwrap_or_default().as_secs();
+                    let stuck = if tx.is_stuck() { ", stuck" } else { "" };
+                    format!(
+                        "{} {} {} (sent {}s ago{})",
+                        tx.chain, tx.hash, tx.summary, age, stuck
+                    )
+                });
+                let unchecked = txs.unchecked.iter().map(|(tx, error)| {
+                    format!(
+                        "{} {} {} (not checked: {})",
+                        tx.chain, tx.hash, tx.summary, error
+                    )
+                });
+                let lines: Vec<_> = pending.chain(unchecked).collect();
+                match lines.is_empty() {
+                    true => "no pending transactions".to_string(),
+                    false => lines.join("\n"),
+                }
+            }
+            _ => HELP.to_string(),
+        };
+        Some(reply)
+    }
+
+    async fn turn(&mut self, agent: &impl Chat, line: &str) -> Result<String> {
+        let prompt = match &self.chain {
+            Some(chain) => format!("{}\n(Use chain {} unless stated otherwise.)", line, chain),
+            None => line.to_string(),
+        };
+        let response = agent.chat(&prompt, self.history.clone()).await?;
+        self.history.push(Message {
+            role: "user".to_string(),
+            content: prompt,
+        });
+        self.history.push(Message {
+            role: "assistant".to_string(),
+            content: response.clone(),
+        });
+        Ok(response)
+    }
+}
+
+pub async fn run(agent: &impl Chat, model: &str) -> Result<()> {
+    let mut session = ReplSession::default();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    println!("{}", HELP);
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next_line().await? else {
+            return Ok(());
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('/') || line == "exit" || line == "quit" {
+            match session
+                .command(&format!("/{}", line.trim_start_matches('/')))
+                .await
+            {
+                Some(reply) => println!("{}", reply),
+                None => return Ok(()),
+            }
+            continue;
+        }
+        let turn = audit::scope(model, line, session.turn(agent, line))
+            .instrument(info_span!("agent_prompt", model));
+        match turn.await {
+            Ok(response) => println!("{}", render(&response)),
+            Err(e) => println!("error: {}", e),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_repl_commands() {
+    // Its own tracker, so transactions other tests send never show up.
+    let mut session = ReplSession {
+        tracker: Box::leak(Box::default()),
+        ..Default::default()
+    };
+    session.history.push(Message {
+        role: "user".to_string(),
+        content: "hello".to_string(),
+    });
+
+    assert_eq!(
+        session.command("/chain Base").await.unwrap(),
+        "default chain: base"
+    );
+    assert!(session
+        .command("/chain basee")
+        .await
+        .unwrap()
+        .contains("did you mean: base"));
+    assert_eq!(session.chain.as_deref(), Some("base"));
+    assert_eq!(session.command("/clear").await.unwrap(), "history cleared");
+    assert!(session.history.is_empty());
+    assert_eq!(
+        session.command("/pending").await.unwrap(),
+        "no pending transactions"
+    );
+    assert!(session.command("/exit").await.is_none());
+}
@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use rig::{
+    agent::{Agent, AgentBuilder},
+    completion::{Chat, CompletionModel, Message, Prompt, PromptError},
+};
+use serde::Deserialize;
+
+use crate::{
+    balance::GetBalance,
+    chains::chains_context,
+    erc20_transfer::{self, ERC20Transfer},
+    eth_transfer::{self, ETHTransfer},
+    guard::Guarded,
+    llm::{AgentSpec, LlmAgent, LlmConfig},
+    pending_tools::{CancelTx, ListPendingTxs, SpeedUpTx},
+    registry_tools::{ListChains, ListTokens},
+    swap::{self, EthSwapQuote, EthSwapToERC20},
+};
+
+const CLASSIFIER_PREAMBLE: &str = "You route requests for an EVM chain agent. \
+Classify the user's latest request into one or more of these intents, in the order they should be handled:
+- transfer: send ETH or ERC20 tokens to an address, or speed up or cancel a pending transaction
+- swap: swap ETH for an ERC20 token
+- query: look up chains, tokens, balances, swap quotes or pending transactions without sending anything
+- bridge: move assets from one chain to another
+Reply with only a JSON array of intents, for example [\"swap\", \"transfer\"].";
+
+const QUERY_PREAMBLE: &str = "You are a query agent here to answer questions about chains, \
+token addresses, balances, swap quotes and pending transactions. You never send transactions.";
+
+const REPLACE_PREAMBLE: &str = "Transactions that stay pending can be sped up with speed_up_tx \
+or cancelled with cancel_tx, using the hash from list_pending_txs.";
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Intent {
+    Transfer,
+    Swap,
+    Query,
+    Bridge,
+}
+
+impl Intent {
+    const ALL: [Intent; 4] = [
+        Intent::Transfer,
+        Intent::Swap,
+        Intent::Query,
+        Intent::Bridge,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Intent::Transfer => "transfer",
+            Intent::Swap => "swap",
+            Intent::Query => "query",
+            Intent::Bridge => "bridge",
+        }
+    }
+}
+
+/// Read the classifier's answer. Falls back to keyword matching when the model
+/// does not return clean JSON, and to `query` when nothing matches.
+pub fn parse_intents(response: &str) -> Vec<Intent> {
+    let json = response
+        .find('[')
+        .zip(response.rfind(']'))
+        .and_then(|(start, end)| serde_json::from_str::<Vec<Intent>>(&response[start..=end]).ok());
+    let mut intents = json.unwrap_or_else(|| {
+        let lower = response.to_lowercase();
+        let mut found: Vec<(usize, Intent)> = Intent::ALL
+            .into_iter()
+            .filter_map(|intent| lower.find(intent.name()).map(|pos| (pos, intent)))
+            .collect();
+        found.sort_by_key(|(pos, _)| *pos);
+        found.into_iter().map(|(_, intent)| intent).collect()
+    });
+    // Each intent once, in the order it first came up.
+    let mut seen = HashSet::new();
+    intents.retain(|intent| seen.insert(*intent));
+    if intents.is_empty() {
+        intents.push(Intent::Query);
+    }
+    intents
+}
+
+struct Classifier;
+impl AgentSpec for Classifier {
+    fn build<M: CompletionModel>(&self, builder: AgentBuilder<M>) -> Agent<M> {
+        builder.preamble(CLASSIFIER_PREAMBLE).build()
+    }
+}
+
+/// A sub-agent with only the tools for one intent.
+struct SubAgent(Intent);
+impl AgentSpec for SubAgent {
+    fn build<M: CompletionModel>(&self, builder: AgentBuilder<M>) -> Agent<M> {
+        let builder = builder
+            .context(&chains_context())
+            .tool(Guarded(ListChains))
+            .tool(Guarded(ListTokens))
+            .tool(Guarded(GetBalance));
+        match self.0 {
+            Intent::Transfer => builder
+                .preamble(erc20_transfer::PREAMBLE)
+                .append_preamble(eth_transfer::PREAMBLE)
+                .append_preamble(REPLACE_PREAMBLE)
+                .tool(Guarded(ERC20Transfer))
+                .tool(Guarded(ETHTransfer))
+                .tool(Guarded(ListPendingTxs))
+                .tool(Guarded(SpeedUpTx))
+                .tool(Guarded(CancelTx))
+                .build(),
+            Intent::Swap => builder
+                .preamble(swap::PREAMBLE)
+                .tool(Guarded(EthSwapQuote))
+                .tool(Guarded(EthSwapToERC20))
+                .build(),
+            Intent::Query | Intent::Bridge => builder
+                .preamble(QUERY_PREAMBLE)
+                .tool(Guarded(EthSwapQuote))
+                .tool(Guarded(ListPendingTxs))
+                .build(),
+        }
+    }
+}
+
+/// Top-level agent that classifies each request and delegates it to sub-agents.
+pub struct Router<A> {
+    classifier: A,
+    transfer: A,
+    swap: A,
+    query: A,
+}
+
+impl Router<LlmAgent> {
+    pub fn from_config(config: &LlmConfig) -> Result<Self> {
+        Ok(Router {
+            classifier: config.build_agent(&Classifier)?,
+            transfer: config.build_agent(&SubAgent(Intent::Transfer))?,
+            swap: config.build_agent(&SubAgent(Intent::Swap))?,
+            query: config.build_agent(&SubAgent(Intent::Query))?,
+        })
+    }
+}
+
+impl<A: Chat> Router<A> {
+    async fn route(&self, prompt: &str, chat_history: Vec<Message>) -> Result<String, PromptError> {
+        let intents = parse_intents(&self.classifier.chat(prompt, chat_history.clone()).await?);
+        if let [intent] = intents[..] {
+            return self
+                .delegate(intent, prompt.to_string(), chat_history)
+                .await;
+        }
+
+        // Several intents: each sub-agent handles its part and sees the earlier answers.
+        let mut history = chat_history;
+        let mut answers = Vec::new();
+        for intent in intents {
+            let part = format!(
+                "{}\n(Only handle the {} part of this request.)",
+                prompt,
+                intent.name()
+            );
+            let answer = self.delegate(intent, part.clone(), history.clone()).await?;
+            history.push(Message {
+                role: "user".to_string(),
+                content: part,
+            });
+            history.push(Message {
+                role: "assistant".to_string(),
+                content: answer.clone(),
+            });
+            answers.push(format!("[{}] {}", intent.name(), answer));
+        }
+        Ok(answers.join("\n"))
+    }
+
+    async fn delegate(
+        &self,
+        intent: Intent,
+        prompt: String,
+        chat_history: Vec<Message>,
+    ) -> Result<String, PromptError> {
+        match intent {
+            Intent::Transfer => self.transfer.chat(&prompt, chat_history).await,
+            Intent::Swap => self.swap.chat(&prompt, chat_history).await,
+            Intent::Query => self.query.chat(&prompt, chat_history).await,
+            Intent::Bridge => Ok("Bridging between chains is not supported yet.".to_string()),
+        }
+    }
+}
+
+impl<A: Chat> Prompt for Router<A> {
+    async fn prompt(&self, prompt: &str) -> Result<String, PromptError> {
+        self.route(prompt, vec![]).await
+    }
+}
+
+impl<A: Chat> Chat for Router<A> {
+    async fn chat(&self, prompt: &str, chat_history: Vec<Message>) -> Result<String, PromptError> {
+        self.route(prompt, chat_history).await
+    }
+}
+
+#[test]
+fn test_parse_intents() {
+    assert_eq!(
+        parse_intents(r#"["swap", "transfer"]"#),
+        vec![Intent::Swap, Intent::Transfer]
+    );
+    assert_eq!(
+        parse_intents("```json\n[\"query\"]\n```"),
+        vec![Intent::Query]
+    );
+    assert_eq!(
+        parse_intents("This is a transfer, then a bridge."),
+        vec![Intent::Transfer, Intent::Bridge]
+    );
+    assert_eq!(
+        parse_intents(r#"["swap", "transfer", "swap"]"#),
+        vec![Intent::Swap, Intent::Transfer]
+    );
+    assert_eq!(parse_intents("no idea"), vec![Intent::Query]);
+}
+
+#[tokio::test]
+async fn test_route_multiple_intents() {
+    // Replies with its own name, or verbatim when it is the classifier's JSON.
+    struct Stub(&'static str);
+    impl Chat for Stub {
+        async fn chat(&self, prompt: &str, history: Vec<Message>) -> Result<String, PromptError> {
+            if self.0.starts_with('[') {
+                return Ok(self.0.to_string());
+            }
+            let first_line = prompt.lines().next().unwrap_or_default();
+            Ok(format!(
+                "{} after {} messages: {}",
+                self.0,
+                history.len(),
+                first_line
+            ))
+        }
+    }
+
+    let router = Router {
+        classifier: Stub(r#"["swap", "transfer"]"#),
+        transfer: Stub("transfer agent"),
+        swap: Stub("swap agent"),
+        query: Stub("query agent"),
+    };
+    let answer = router
+        .prompt("swap 0.1 ETH to USDC then send it to bob")
+        .await
+        .unwrap();
+    assert_eq!(
+        answer,
+        "[swap] swap agent after 0 messages: swap 0.1 ETH to USDC then send it to bob\n\
+         [transfer] transfer agent after 2 messages: swap 0.1 ETH to USDC then send it to bob"
+    );
+}
@@ -0,0 +1,550 @@
+use std::{convert::Infallible, net::SocketAddr, str::FromStr, sync::Arc};
+
+use alloy::primitives::B256;
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{delete, get, post},
+    Json, Router,
+};
+use rig::{
+    completion::{Chat, Message, Prompt, PromptError},
+    tool::ToolSet,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{net::TcpListener, sync::mpsc};
+use tokio_stream::{wrappers::UnboundedReceiverStream, Stream, StreamExt};
+use tracing::{info, info_span, Instrument};
+
+use crate::{
+    agent::{tool_definitions, toolset},
+    audit::{self, AuditRecord},
+    chains::get_chain_info,
+    proposal::{Proposal, ProposalError, ProposalStatus, ProposalStore},
+    tx_output::{render, TxOutput},
+    tx_tracker::{tx_state, TxState},
+    users::{self, PermissionError, Users},
+};
+
+struct AppState<A> {
+    agent: A,
+    model: String,
+    tools: ToolSet,
+    /// `None` leaves the API open.
+    users: Option<Users>,
+}
+
+#[derive(Deserialize)]
+pub struct PromptRequest {
+    prompt: String,
+}
+
+#[derive(Serialize)]
+pub struct PromptResponse {
+    /// The agent's answer as is: a tool's JSON output or plain text.
+    response: String,
+    /// The answer rendered for people, as the CLI prints it.
+    text: String,
+}
+
+impl PromptResponse {
+    fn new(response: String) -> Self {
+        PromptResponse {
+            text: render(&response),
+            response,
+        }
+    }
+}
+
+/// Error body of every endpoint: `{"error": "..."}`.
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl ToString) -> Self {
+        ApiError {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<ProposalError> for ApiError {
+    fn from(e: ProposalError) -> Self {
+        let status = match &e {
+            ProposalError::NotFound(_) => StatusCode::NOT_FOUND,
+            ProposalError::NotPending { .. } => StatusCode::CONFLICT,
+            ProposalError::UnknownProposer { .. } => StatusCode::FORBIDDEN,
+            ProposalError::Send(e) if e.downcast_ref::<PermissionError>().is_some() => {
+                StatusCode::FORBIDDEN
+            }
+            ProposalError::Send(_) => StatusCode::BAD_GATEWAY,
+            // The store could not be read or written.
+            ProposalError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError::new(status, format!("{:#}", e))
+    }
+}
+
+impl From<PermissionError> for ApiError {
+    fn from(e: PermissionError) -> Self {
+        ApiError::new(StatusCode::FORBIDDEN, e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+/// Routes of the HTTP API. With `users`, every request needs an `Authorization:
+/// Bearer <token>` header and runs with that user's permissions, wallet and limits.
+/// - `POST /prompt` with `{"prompt": "..."}`: the agent's answer. Authenticated
+///   users keep a conversation, the open API answers each prompt on its own.
+/// - `POST /prompt/stream`: server-sent events, a `tool_call` event with the audit
+///   record of each tool call as it finishes, then `response` or `error`.
+/// - `DELETE /history`: forget the user's conversation.
+/// - `GET /tools`: definitions of the tools the user may call.
+/// - `POST /tools/{name}` with the tool's JSON arguments: the tool's output.
+/// - `GET /tx/{chain}/{hash}`: status of a transaction.
+/// - `GET /proposals?status=pending`: proposed transactions, all of them without `status`.
+/// - `POST /proposals/{id}/approve`: sign and send a proposal; the tool output.
+/// - `POST /proposals/{id}/reject`: discard a proposal.
+pub fn router<A: Prompt + Chat + 'static>(agent: A, model: &str, users: Option<Users>) -> Router {
+    let state = Arc::new(AppState {
+        agent,
+        model: model.to_string(),
+        tools: toolset(),
+        users,
+    });
+    Router::new()
+        .route("/prompt", post(prompt::<A>))
+        .route("/prompt/stream", post(prompt_stream::<A>))
+        .route("/history", delete(clear_history))
+        .route("/tools", get(list_tools))
+        .route("/tools/{name}", post(call_tool::<A>))
+        .route("/tx/{chain}/{hash}", get(tx_status))
+        .route("/proposals", get(list_proposals))
+        .route("/proposals/{id}/approve", post(approve_proposal))
+        .route("/proposals/{id}/reject", post(reject_proposal))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            authenticate::<A>,
+        ))
+        .with_state(state)
+}
+
+/// Serve the API on `addr`. Without `users` anyone who can reach it signs with
+/// the local account, so it only binds to loopback addresses then.
+pub async fn serve<A: Prompt + Chat + 'static>(
+    addr: SocketAddr,
+    agent: A,
+    model: &str,
+    users: Option<Users>,
+) -> Result<()> {
+    if users.is_none() && !addr.ip().is_loopback() {
+        return Err(anyhow!(
+            "refusing to serve the open API on {}: pass --users, or listen on 127.0.0.1",
+            addr
+        ));
+    }
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("bind {}", addr))?;
+    info!("listening on http://{}", listener.local_addr()?);
+    axum::serve(listener, router(agent, model, users)).await?;
+    Ok(())
+}
+
+/// Run the request as the user its bearer token belongs to.
+async fn authenticate<A>(
+    State(state): State<Arc<AppState<A>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(users) = &state.users else {
+        return next.run(request).await;
+    };
+    let user = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| users.authenticate(token));
+    match user {
+        Some(user) => users::scope(user, next.run(request)).await,
+        None => {
+            ApiError::new(StatusCode::UNAUTHORIZED, "missing or invalid API token").into_response()
+        }
+    }
+}
+
+/// The agent's answer, continuing the current user's conversation if there is one.
+async fn answer<A: Prompt + Chat>(agent: &A, prompt: &str) -> Result<String, PromptError> {
+    let Some(user) = users::current() else {
+        return agent.prompt(prompt).await;
+    };
+    let mut history = user.history.lock().await;
+    let response = agent.chat(prompt, history.clone()).await?;
+    history.push(Message {
+        role: "user".to_string(),
+        content: prompt.to_string(),
+    });
+    history.push(Message {
+        role: "assistant".to_string(),
+        content: response.clone(),
+    });
+    Ok(response)
+}
+
+async fn prompt<A: Prompt + Chat>(
+    State(state): State<Arc<AppState<A>>>,
+    Json(request): Json<PromptRequest>,
+) -> Result<Json<PromptResponse>, ApiError> {
+    let response = audit::scope(
+        &state.model,
+        &request.prompt,
+        answer(&state.agent, &request.prompt),
+    )
+    .instrument(info_span!("agent_prompt", model = state.model))
+    .await
+    .map_err(|e| ApiError::new(StatusCode::BAD_GATEWAY, e))?;
+    Ok(Json(PromptResponse::new(response)))
+}
+
+async fn prompt_stream<A: Prompt + Chat + 'static>(
+    State(state): State<Arc<AppState<A>>>,
+    Json(request): Json<PromptRequest>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let (events, receiver) = mpsc::unbounded_channel();
+    let user = users::current();
+    let stream = async move {
+        let (records_tx, mut records) = mpsc::unbounded_channel();
+        let answer = audit::scope_with_events(
+            &state.model,
+            &request.prompt,
+            records_tx,
+            answer(&state.agent, &request.prompt),
+        )
+        .instrument(info_span!("agent_prompt", model = state.model));
+        tokio::pin!(answer);
+        let result = loop {
+            tokio::select! {
+                Some(record) = records.recv() => {
+                    let _ = events.send(tool_call_event(&record));
+                }
+                result = &mut answer => break result,
+            }
+        };
+        while let Ok(record) = records.try_recv() {
+            let _ = events.send(tool_call_event(&record));
+        }
+        let last = match result {
+            Ok(response) => Event::default()
+                .event("response")
+                .json_data(PromptResponse::new(response)),
+            Err(e) => Event::default()
+                .event("error")
+                .json_data(json!({ "error": e.to_string() })),
+        };
+        let _ = events.send(last.expect("event serializes"));
+    };
+    // The spawned task does not inherit the request's user.
+    match user {
+        Some(user) => tokio::spawn(users::scope(user, stream)),
+        None => tokio::spawn(stream),
+    };
+    Sse::new(UnboundedReceiverStream::new(receiver).map(Ok)).keep_alive(KeepAlive::default())
+}
+
+fn tool_call_event(record: &AuditRecord) -> Event {
+    Event::default()
+        .event("tool_call")
+        .json_data(record)
+        .expect("audit record serializes")
+}
+
+async fn clear_history() -> StatusCode {
+    if let Some(user) = users::current() {
+        user.history.lock().await.clear();
+    }
+    StatusCode::NO_CONTENT
+}
+
+async fn list_tools() -> Json<Value> {
+    let mut tools = tool_definitions().await;
+    if let Some(user) = users::current() {
+        tools.retain(|tool| user.allows_tool(&tool.name));
+    }
+    Json(json!(tools))
+}
+
+async fn call_tool<A>(
+    State(state): State<Arc<AppState<A>>>,
+    Path(name): Path<String>,
+    Json(args): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    if !state.tools.contains(&name) {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            format!("unknown tool '{}'", name),
+        ));
+    }
+    // The tools check the user's permissions themselves.
+    let output =
+        state
+            .tools
+            .call(&name, args.to_string())
+            .await
+            .map_err(|e| match permission_error(&e) {
+                Some(e) => ApiError::new(StatusCode::FORBIDDEN, e),
+                None => ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, e),
+            })?;
+    // Tools return their output serialized as JSON.
+    let output = serde_json::from_str(&output).unwrap_or(Value::String(output));
+    Ok(Json(output))
+}
+
+// The permission a tool call was refused for, somewhere in the error's sources.
+fn permission_error<'a>(e: &'a (dyn std::error::Error + 'static)) -> Option<&'a PermissionError> {
+    std::iter::successors(Some(e), |e| e.source()).find_map(|e| e.downcast_ref())
+}
+
+async fn tx_status(Path((chain, hash)): Path<(String, String)>) -> Result<Json<TxState>, ApiError> {
+    let chain_info = get_chain_info(&chain).map_err(|e| ApiError::new(StatusCode::NOT_FOUND, e))?;
+    if let Some(user) = users::current() {
+        user.check_chain(&chain_info.chain)?;
+    }
+    let hash = B256::from_str(&hash)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("invalid hash: {}", e)))?;
+    match tx_state(&chain_info, hash).await {
+        Ok(Some(state)) => Ok(Json(state)),
+        Ok(None) => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            format!("transaction {} not found on {}", hash, chain_info.chain),
+        )),
+        Err(e) => Err(ApiError::new(StatusCode::BAD_GATEWAY, format!("{:#}", e))),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ProposalQuery {
+    status: Option<ProposalStatus>,
+}
+
+async fn list_proposals(
+    Query(query): Query<ProposalQuery>,
+) -> Result<Json<Vec<Proposal>>, ApiError> {
+    Ok(Json(ProposalStore::default().list(query.status)?))
+}
+
+async fn approve_proposal(Path(id): Path<String>) -> Result<Json<TxOutput>, ApiError> {
+    Ok(Json(ProposalStore::default().approve(&id).await?))
+}
+
+async fn reject_proposal(Path(id): Path<String>) -> Result<Json<Proposal>, ApiError> {
+    Ok(Json(ProposalStore::default().reject(&id)?))
+}
+
+#[cfg(test)]
+async fn send_as(
+    app: &Router,
+    token: Option<&str>,
+    method: &str,
+    uri: &str,
+    body: Value,
+) -> (StatusCode, String) {
+    use tower::ServiceExt;
+
+    let mut request = axum::http::Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+    if let Some(token) = token {
+        request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+    }
+    let request = request
+        .body(axum::body::Body::from(body.to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[cfg(test)]
+async fn send(app: &Router, method: &str, uri: &str, body: Value) -> (StatusCode, String) {
+    send_as(app, None, method, uri, body).await
+}
+
+#[tokio::test]
+async fn test_http_api() {
+    use crate::{
+        agent::EthAgent,
+        llm::AgentSpec,
+        mock_llm::{MockModel, MockStep},
+    };
+    use rig::agent::AgentBuilder;
+
+    let usdc = "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913";
+    let list_usdc = MockStep::tool_call("list_tokens", json!({"chain": "base", "symbol": "USDC"}));
+    let model = MockModel::new(vec![list_usdc.clone(), list_usdc]);
+    let app = router(EthAgent.build(AgentBuilder::new(model)), "mock", None);
+
+    let (status, body) = send(&app, "GET", "/tools", Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let tools: Vec<Value> = serde_json::from_str(&body).unwrap();
+    assert_eq!(tools.len(), crate::agent::TOOL_NAMES.len());
+
+    let (status, body) = send(
+        &app,
+        "POST",
+        "/tools/list_tokens",
+        json!({"chain": "base", "symbol": "USDC"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let output: Value = serde_json::from_str(&body).unwrap();
+    assert!(output.as_str().unwrap().contains(usdc));
+    let (status, _) = send(&app, "POST", "/tools/bridge", json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, body) = send(&app, "POST", "/tools/list_tokens", json!({"chain": 1})).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body.contains("invalid arguments"));
+
+    let prompt = json!({"prompt": "What is the USDC address on base?"});
+    let (status, body) = send(&app, "POST", "/prompt", prompt.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(usdc));
+
+    let (status, body) = send(&app, "POST", "/prompt/stream", prompt).await;
+    assert_eq!(status, StatusCode::OK);
+    let tool_call = body.find("event: tool_call").unwrap();
+    let response = body.find("event: response").unwrap();
+    assert!(tool_call < response);
+    assert!(body.contains(r#""tool":"list_tokens""#));
+
+    let (status, _) = send(&app, "GET", "/tx/base/0x1234", Value::Null).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = send(
+        &app,
+        "GET",
+        &format!("/tx/solana/{}", B256::ZERO),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send(&app, "POST", "/proposals/nope/approve", Value::Null).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    // Only what the node did wrong is a bad gateway.
+    let status = |e| ApiError::from(e).status;
+    assert_eq!(
+        status(ProposalError::Send(anyhow::anyhow!("nonce too low"))),
+        StatusCode::BAD_GATEWAY
+    );
+    assert_eq!(
+        status(ProposalError::Other(anyhow::anyhow!("disk full"))),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+    let (status, _) = send(&app, "GET", "/proposals?status=pending", Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, "GET", "/proposals?status=lost", Value::Null).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_http_users() {
+    use crate::{
+        agent::EthAgent,
+        llm::AgentSpec,
+        mock_llm::{MockModel, MockStep},
+        users::UsersConfig,
+    };
+    use rig::agent::AgentBuilder;
+
+    crate::users::set_test_key();
+    let config: UsersConfig = serde_json::from_str(
+        r#"{
+            "users": {
+                "alice": {"token": "alice-token", "chains": ["base"], "tools": ["list_tokens"],
+                          "private_key_env": "RIG_ETH_TEST_KEY"},
+                "bob": {"token": "bob-token", "private_key_env": "RIG_ETH_TEST_KEY"}
+            }
+        }"#,
+    )
+    .unwrap();
+    let users = Users::from_config(config).unwrap();
+    let alice = users.authenticate("alice-token").unwrap();
+    let list_usdc = MockStep::tool_call("list_tokens", json!({"chain": "base", "symbol": "USDC"}));
+    let model = MockModel::new(vec![list_usdc.clone(), list_usdc]);
+    let app = router(
+        EthAgent.build(AgentBuilder::new(model)),
+        "mock",
+        Some(users),
+    );
+
+    let (status, _) = send(&app, "GET", "/tools", Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = send_as(&app, Some("carol-token"), "GET", "/tools", Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (_, body) = send_as(&app, Some("alice-token"), "GET", "/tools", Value::Null).await;
+    assert_eq!(serde_json::from_str::<Vec<Value>>(&body).unwrap().len(), 1);
+    let (_, body) = send_as(&app, Some("bob-token"), "GET", "/tools", Value::Null).await;
+    assert_eq!(
+        serde_json::from_str::<Vec<Value>>(&body).unwrap().len(),
+        crate::agent::TOOL_NAMES.len()
+    );
+
+    let alice_token = Some("alice-token");
+    let (status, _) = send_as(&app, alice_token, "POST", "/tools/get_balance", json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let args = json!({"chain": "arbitrum", "symbol": "USDC"});
+    let (status, body) = send_as(&app, alice_token, "POST", "/tools/list_tokens", args).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body.contains("may not use chain arbitrum"));
+    let args = json!({"chain": "base", "symbol": "USDC"});
+    let (status, _) = send_as(&app, alice_token, "POST", "/tools/list_tokens", args).await;
+    assert_eq!(status, StatusCode::OK);
+
+    for prompt in ["USDC on base?", "And again?"] {
+        let (status, _) = send_as(
+            &app,
+            alice_token,
+            "POST",
+            "/prompt",
+            json!({ "prompt": prompt }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    assert_eq!(alice.history.lock().await.len(), 4);
+    let (status, _) = send_as(&app, alice_token, "DELETE", "/history", Value::Null).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(alice.history.lock().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_open_api_loopback_only() {
+    use crate::{agent::EthAgent, llm::AgentSpec, mock_llm::MockModel};
+    use rig::agent::AgentBuilder;
+
+    let agent = EthAgent.build(AgentBuilder::new(MockModel::new(vec![])));
+    let error = serve("0.0.0.0:0".parse().unwrap(), agent, "mock", None)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("--users"));
+}
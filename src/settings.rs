@@ -0,0 +1,132 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use once_cell::sync::OnceCell;
+
+const DEFAULT_CONFIG_PATH: &str = "configs/chains.json";
+const DEFAULT_AUDIT_LOG_PATH: &str = "logs/audit.jsonl";
+const DEFAULT_PROPOSALS_PATH: &str = "data/proposals.json";
+const DEFAULT_NONCES_PATH: &str = "data/nonces.json";
+pub const DEFAULT_PROPOSAL_TTL_SECS: u64 = 600;
+pub const DEFAULT_STUCK_AFTER_SECS: u64 = 180;
+
+static CONFIG_PATH: OnceCell<PathBuf> = OnceCell::new();
+static AUDIT_LOG_PATH: OnceCell<PathBuf> = OnceCell::new();
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+static PROPOSALS_PATH: OnceCell<PathBuf> = OnceCell::new();
+static REQUIRE_APPROVAL: AtomicBool = AtomicBool::new(false);
+static PROPOSAL_TTL_SECS: AtomicU64 = AtomicU64::new(DEFAULT_PROPOSAL_TTL_SECS);
+static NONCES_PATH: OnceCell<PathBuf> = OnceCell::new();
+static STUCK_AFTER_SECS: AtomicU64 = AtomicU64::new(DEFAULT_STUCK_AFTER_SECS);
+
+/// Override the chain config path. Must be called before the chain registry is first used.
+pub fn set_config_path(path: PathBuf) {
+    let _ = CONFIG_PATH.set(path);
+}
+
+pub fn config_path() -> &'static Path {
+    CONFIG_PATH.get_or_init(|| PathBuf::from(DEFAULT_CONFIG_PATH))
+}
+
+/// Override where tool invocations are audited. Must be called before the first tool call.
+pub fn set_audit_log_path(path: PathBuf) {
+    let _ = AUDIT_LOG_PATH.set(path);
+}
+
+pub fn audit_log_path() -> &'static Path {
+    AUDIT_LOG_PATH.get_or_init(|| {
+        if cfg!(test) {
+            std::env::temp_dir().join("rig-eth-test-audit.jsonl")
+        } else {
+            PathBuf::from(DEFAULT_AUDIT_LOG_PATH)
+        }
+    })
+}
+
+/// In dry-run mode tools validate their arguments and estimate the cost, but never sign or send.
+pub fn set_dry_run(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::Relaxed);
+}
+
+pub fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Override where proposed transactions are stored. Must be called before the first proposal.
+pub fn set_proposals_path(path: PathBuf) {
+    let _ = PROPOSALS_PATH.set(path);
+}
+
+pub fn proposals_path() -> &'static Path {
+    PROPOSALS_PATH.get_or_init(|| {
+        if cfg!(test) {
+            std::env::temp_dir().join("rig-eth-test-proposals.json")
+        } else {
+            PathBuf::from(DEFAULT_PROPOSALS_PATH)
+        }
+    })
+}
+
+/// When approval is required, transaction tools store a proposal instead of signing,
+/// and nothing is sent until it is approved.
+pub fn set_approval_required(enabled: bool) {
+    REQUIRE_APPROVAL.store(enabled, Ordering::Relaxed);
+}
+
+pub fn approval_required() -> bool {
+    REQUIRE_APPROVAL.load(Ordering::Relaxed)
+}
+
+/// How long a proposal can be approved for.
+pub fn set_proposal_ttl(ttl: Duration) {
+    PROPOSAL_TTL_SECS.store(ttl.as_secs(), Ordering::Relaxed);
+}
+
+pub fn proposal_ttl() -> Duration {
+    Duration::from_secs(PROPOSAL_TTL_SECS.load(Ordering::Relaxed))
+}
+
+/// Override where nonces in flight are kept. Must be called before the first transaction.
+pub fn set_nonces_path(path: PathBuf) {
+    let _ = NONCES_PATH.set(path);
+}
+
+pub fn nonces_path() -> &'static Path {
+    NONCES_PATH.get_or_init(|| {
+        if cfg!(test) {
+            std::env::temp_dir().join("rig-eth-test-nonces.json")
+        } else {
+            PathBuf::from(DEFAULT_NONCES_PATH)
+        }
+    })
+}
+
+/// Where sent transactions are tracked until they are mined, next to the nonces.
+pub fn sent_txs_path() -> PathBuf {
+    nonces_path().with_file_name(if cfg!(test) {
+        "rig-eth-test-sent-txs.json"
+    } else {
+        "sent_txs.json"
+    })
+}
+
+/// Where what API users sent is counted against their daily limits, next to the nonces.
+pub fn spent_path() -> PathBuf {
+    nonces_path().with_file_name(if cfg!(test) {
+        "rig-eth-test-spent.json"
+    } else {
+        "spent.json"
+    })
+}
+
+/// How long a sent transaction can go without a receipt before it counts as stuck.
+pub fn set_stuck_after(threshold: Duration) {
+    STUCK_AFTER_SECS.store(threshold.as_secs(), Ordering::Relaxed);
+}
+
+pub fn stuck_after() -> Duration {
+    Duration::from_secs(STUCK_AFTER_SECS.load(Ordering::Relaxed))
+}
@@ -1,29 +1,59 @@
-use crate::chains::get_chain_info;
+use crate::{
+    chains::{get_chain_info, ChainInfo},
+    erc20_transfer::IERC20,
+    fee::{estimate_cost, FeeTier},
+    metrics,
+    proposal::propose,
+    settings::{approval_required, dry_run, proposal_ttl},
+    telemetry::rpc,
+    tool_schema::{
+        address_schema, amount_schema, chain_schema, parameters, parse_amount, whole_units,
+    },
+    tx_output::TxOutput,
+    tx_tracker::TX_TRACKER,
+    wallet::{local_address, send_transaction},
+};
 use alloy::{
-    network::EthereumWallet,
-    primitives::{utils::parse_ether, Address, TxHash, B256, U256},
-    providers::{ProviderBuilder, RootProvider, WalletProvider},
-    signers::local::PrivateKeySigner,
+    primitives::{
+        utils::{format_units, parse_ether},
+        Address, U256,
+    },
+    providers::{ProviderBuilder, RootProvider},
+    rpc::types::TransactionRequest,
     sol,
     transports::http::{Client, Http},
 };
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use rig::{completion::ToolDefinition, tool::Tool};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::{str::FromStr, sync::Arc};
+use std::str::FromStr;
+use tracing::{info, warn};
 
 const MAX_AMOUNT: u128 = 10u128;
 
-#[derive(Deserialize)]
+pub const PREAMBLE: &str =
+    "You are a swap agent here to help the user perform ETH to ERC20 token swaps.";
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct SwapArgs {
+    /// The chain name, alias or chain ID, such as arbitrum
+    #[schemars(schema_with = "chain_schema")]
     chain: String,
+    /// The address of the ERC20 token to receive
+    #[schemars(schema_with = "address_schema")]
     token_address: String,
-    amount: String, // Amount of ETH to swap
+    /// The amount of ETH to swap
+    #[schemars(schema_with = "amount_schema")]
+    amount: String,
+    /// How fast the transaction should be included: slow pays the least, fast the most
+    #[serde(default)]
+    speed: FeeTier,
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error("Swap error")]
+#[error("Swap error: {message}")]
 pub struct SwapError {
     message: String,
 }
@@ -44,46 +74,30 @@ impl Tool for EthSwapToERC20 {
 
     type Error = SwapError;
     type Args = SwapArgs;
-    type Output = String;
+    type Output = TxOutput;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
             name: "eth_swap_to_erc20".to_string(),
             description: "Swap ETH for a specific ERC20 token".to_string(),
-            parameters: json!({
-                "type": "object",
-                "properties": {
-                    "token_address": {
-                        "type": "string",
-                        "description": "The address of the ERC20 token to receive"
-                    },
-                    "chain": {
-                        "type": "string",
-                        "description": "The chain name, such as arbitrum"
-                    },
-                    "amount": {
-                        "type": "string",
-                        "description": "The amount of ETH to swap"
-                    }
-                }
-            }),
+            parameters: parameters::<SwapArgs>(),
         }
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
         let chain_name = args.chain;
-        let token_address = Address::from_str(&args.token_address).unwrap();
-        let amount = u128::from_str(&args.amount).unwrap_or_default();
-        println!(
-            "chain_name: {}, token_address: {}, amount: {}",
-            chain_name, token_address, amount
-        );
-
-        if amount > MAX_AMOUNT {
-            println!(
-                "amount = {} exceeds the safe value = {}",
-                amount, MAX_AMOUNT
-            );
+        let token_address = Address::from_str(&args.token_address).map_err(|e| SwapError {
+            message: format!("invalid token_address: {}", e),
+        })?;
+        let amount = &args.amount;
+        info!(chain = %chain_name, %token_address, %amount, "eth swap");
+        let amount_in = parse_amount(amount, 18).map_err(|e| SwapError {
+            message: e.to_string(),
+        })?;
+
+        if amount_in > whole_units(MAX_AMOUNT, 18) {
+            warn!(%amount, max = MAX_AMOUNT, "amount exceeds the safe value");
+            metrics::policy_rejection(Self::NAME, "amount_limit");
             return Err(SwapError {
                 message: format!(
                     "amount = {} exceeds the safe value = {}",
@@ -93,20 +107,48 @@ impl Tool for EthSwapToERC20 {
             });
         }
 
-        let chain_info = get_chain_info(&chain_name).ok_or(SwapError {
-            message: "get_chain_info none".to_string(),
+        let chain_info = get_chain_info(&chain_name).map_err(|e| SwapError {
+            message: e.to_string(),
         })?;
-        let weth = chain_info.tokens.iter().find(|t| t.0 == "WETH").unwrap().1;
-        let path: Vec<Address> = vec![Address::from_str(&weth).unwrap(), token_address]; // ETH -> Token
-        let result = swap_eth_to_erc20(
-            Address::from_str(&chain_info.swap_router).unwrap(),
-            parse_ether(&args.amount).unwrap_or_default(),
+        let path = swap_path(&chain_info, token_address)?;
+
+        let summary = format!("swap {} ETH for token {}", args.amount, token_address);
+        let output = TxOutput::new(
+            Self::NAME,
+            summary.clone(),
+            &chain_info,
+            local_address(),
+            Some(token_address),
+            args.amount,
+        )
+        .with_raw_amount(amount_in);
+        let built = swap_eth_to_erc20_tx(
+            swap_router(&chain_info)?,
+            amount_in,
             path,
-            chain_info.provider_url,
+            chain_info.chain.clone(),
+            chain_info.provider_url.clone(),
         )
         .await;
+        let tx = built.map_err(|e| SwapError {
+            message: format!("swap_eth_to_erc20 error: {}", e),
+        })?;
+        let output = output.with_gas_estimate(estimate_cost(&chain_info, &tx, args.speed).await);
+        if dry_run() {
+            return Ok(output);
+        }
+        if approval_required() {
+            return propose(output, tx, args.speed).map_err(|e| SwapError {
+                message: format!("propose error: {:#}", e),
+            });
+        }
+
+        let result = send_transaction(&chain_info, tx, args.speed).await;
         match result {
-            Ok(h) => Ok(h.to_string()),
+            Ok(broadcast) => {
+                TX_TRACKER.record_sent(&chain_info.chain, &broadcast, summary);
+                Ok(output.with_broadcast(&chain_info, broadcast))
+            }
             Err(e) => Err(SwapError {
                 message: format!("swap_eth_to_erc20 error: {}", e),
             }),
@@ -114,111 +156,248 @@ impl Tool for EthSwapToERC20 {
     }
 }
 
-async fn swap_eth_to_erc20(
+// ETH -> Token through the chain's WETH.
+fn swap_path(chain_info: &ChainInfo, token_address: Address) -> Result<Vec<Address>, SwapError> {
+    let weth = chain_info
+        .tokens
+        .get("WETH")
+        .and_then(|weth| Address::from_str(weth).ok())
+        .ok_or(SwapError {
+            message: format!("no WETH configured on {}", chain_info.chain),
+        })?;
+    Ok(vec![weth, token_address])
+}
+
+fn swap_router(chain_info: &ChainInfo) -> Result<Address, SwapError> {
+    Address::from_str(&chain_info.swap_router).map_err(|e| SwapError {
+        message: format!("invalid swap_router on {}: {}", chain_info.chain, e),
+    })
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct EthSwapQuote;
+impl Tool for EthSwapQuote {
+    const NAME: &'static str = "eth_swap_quote";
+
+    type Error = SwapError;
+    type Args = SwapArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "eth_swap_quote".to_string(),
+            description: "Quote how many ERC20 tokens a swap of ETH would return, without sending a transaction".to_string(),
+            parameters: parameters::<SwapArgs>(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let chain_info = get_chain_info(&args.chain).map_err(|e| SwapError {
+            message: e.to_string(),
+        })?;
+        let token_address = Address::from_str(&args.token_address).map_err(|e| SwapError {
+            message: format!("invalid token_address: {}", e),
+        })?;
+        let amount_in = parse_ether(&args.amount).map_err(|e| SwapError {
+            message: format!("invalid amount: {}", e),
+        })?;
+        let path = swap_path(&chain_info, token_address)?;
+
+        let quote = quote_eth_to_erc20(
+            swap_router(&chain_info)?,
+            amount_in,
+            path,
+            chain_info.chain.clone(),
+            chain_info.provider_url,
+        )
+        .await
+        .map_err(|e| SwapError {
+            message: format!("quote_eth_to_erc20 error: {}", e),
+        })?;
+        Ok(format!(
+            "{} ETH -> {} of token {} on {}",
+            args.amount, quote, token_address, chain_info.chain
+        ))
+    }
+}
+
+/// Expected output of a swap, formatted with the token's decimals.
+pub async fn quote_eth_to_erc20(
     router_address: Address,
     amount: U256,
     path: Vec<Address>,
+    chain: String,
     provider_url: String,
-) -> std::result::Result<B256, anyhow::Error> {
-    let private_key = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
-    let signer: PrivateKeySigner = private_key.parse().expect("parse PrivateKeySigner");
-    let wallet: EthereumWallet = EthereumWallet::from(signer.clone());
-
+) -> std::result::Result<String, anyhow::Error> {
     let provider: RootProvider<Http<Client>> =
-        ProviderBuilder::new().on_http(provider_url.parse().expect("parse l1_rpc to Url"));
+        ProviderBuilder::new().on_http(provider_url.parse()?);
+    let token = IERC20::IERC20Instance::new(path[1], provider.clone());
+    let router = IROUTER::IROUTERInstance::new(router_address, provider);
 
-    let eth_signer = Arc::new(
-        ProviderBuilder::new()
-            .with_recommended_fillers()
-            .wallet(wallet)
-            .on_provider(provider.clone()),
-    );
+    let amounts = rpc(
+        &chain,
+        "getAmountsOut",
+        router.getAmountsOut(amount, path).call(),
+    )
+    .await?
+    .amounts;
+    let decimals = rpc(&chain, "decimals", token.decimals().call()).await?._0;
+    Ok(format_units(amount_out(&amounts)?, decimals)?)
+}
+
+// The last amount of a `getAmountsOut` result is what the end of the path receives.
+fn amount_out(amounts: &[U256]) -> Result<U256, SwapError> {
+    match amounts {
+        [_, .., out] => Ok(*out),
+        _ => Err(SwapError {
+            message: format!("router returned {} amounts, expected 2", amounts.len()),
+        }),
+    }
+}
+
+// The swap call, with the minimum output from the router's current quote.
+async fn swap_eth_to_erc20_tx(
+    router_address: Address,
+    amount: U256,
+    path: Vec<Address>,
+    chain: String,
+    provider_url: String,
+) -> std::result::Result<TransactionRequest, anyhow::Error> {
+    let provider: RootProvider<Http<Client>> =
+        ProviderBuilder::new().on_http(provider_url.parse()?);
 
     // Create contract instance.
-    let swap_router_instance = IROUTER::IROUTERInstance::new(router_address, eth_signer.clone());
+    let swap_router_instance = IROUTER::IROUTERInstance::new(router_address, provider);
 
-    // Prepare swap func params.
-    let receive_address = eth_signer.default_signer_address();
+    // Prepare swap func params. A proposal can wait for approval up to its TTL.
+    let receive_address = local_address();
+    let approval_window = if approval_required() {
+        proposal_ttl().as_secs()
+    } else {
+        0
+    };
     let deadline = U256::from(
         std::time::SystemTime::now()
             .duration_since(std::time::SystemTime::UNIX_EPOCH)
             .expect("Time error")
             .as_secs()
+            + approval_window
             + 1200,
     ); // 20 minutes deadline
 
-    let tx_hash: std::result::Result<TxHash, anyhow::Error> = async move {
-        let handle = tokio::task::spawn_blocking(move || {
-            let result = tokio::runtime::Handle::current().block_on(async {
-                
-                let expected_amount: U256 = swap_router_instance
-                    .getAmountsOut(amount, path.clone())
-                    .call()
-                    .await
-                    .unwrap()
-                    .amounts[1];
-                //Calculate amount_out_min (for example, set a slippage of 0.5%)
-                let slippage = U256::from(5); // 0.5%
-                let amount_out_min =
-                    expected_amount * (U256::from(1000) - slippage) / U256::from(1000);
-
-                swap_router_instance
-                    .swapExactETHForTokens(amount_out_min, path, receive_address, deadline)
-                    .send()
-                    .await
-            });
-            result
-        });
-        match handle.await {
-            Ok(Ok(tx)) => Ok(tx.tx_hash().clone()),
-            Ok(Err(e)) => Err(anyhow!(format!("alloy rpc error: {}", e))),
-            Err(e) => Err(anyhow!(format!("tokio exec error: {}", e))),
-        }
-    }
-    .await;
-    tx_hash
+    let amounts = rpc(
+        &chain,
+        "getAmountsOut",
+        swap_router_instance
+            .getAmountsOut(amount, path.clone())
+            .call(),
+    )
+    .await?
+    .amounts;
+    let expected_amount = amount_out(&amounts)?;
+    //Calculate amount_out_min (for example, set a slippage of 0.5%)
+    let slippage = U256::from(5); // 0.5%
+    let amount_out_min = expected_amount * (U256::from(1000) - slippage) / U256::from(1000);
+
+    Ok(swap_router_instance
+        .swapExactETHForTokens(amount_out_min, path, receive_address, deadline)
+        .value(amount)
+        .into_transaction_request())
 }
 
 #[tokio::test]
 async fn test_swap_eth_to_erc20() -> Result<()> {
-    let weth = Address::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap();
-    let expect_token = Address::from_str("5FbDB2315678afecb367f032d93F642f64180aa3").unwrap();
-    let path: Vec<Address> = vec![weth, expect_token]; // ETH -> Token
+    use crate::anvil_harness::AnvilHarness;
+
+    let anvil = AnvilHarness::spawn().await?;
+    let path: Vec<Address> = vec![anvil.weth, anvil.token]; // ETH -> Token
+    let receiver = local_address();
+    let before = anvil.token_balance(receiver).await?;
 
     let amount = "0.1".to_string(); // 0.1 ETH
-    let tx_hash = swap_eth_to_erc20(
-        Address::from_str("0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D").unwrap(),
+    let expected = anvil.quote(parse_ether(&amount)?).await?;
+    let pool_before = anvil.token_balance(anvil.pair).await?;
+    let tx = swap_eth_to_erc20_tx(
+        anvil.router,
         parse_ether(&amount).unwrap(),
         path,
-        String::from("http://localhost:8545"),
+        anvil.chain.clone(),
+        anvil.endpoint(),
     )
-    .await;
-    println!("tx_hash:{}", tx_hash.unwrap().to_string());
+    .await?;
+    let broadcast = send_transaction(&get_chain_info(&anvil.chain)?, tx, FeeTier::Normal).await?;
+    println!("tx_hash:{}", broadcast.hash);
+    assert_eq!(anvil.token_balance(receiver).await? - before, expected);
+    // Paid out of the pair's reserves.
+    assert_eq!(
+        pool_before - anvil.token_balance(anvil.pair).await?,
+        expected
+    );
     Ok(())
 }
 
 #[tokio::test]
 async fn test_run() -> Result<()> {
-    use crate::chains::CHAIN_INFOS;
-    use rig::completion::Prompt;
-    use rig::providers::openai;
+    use crate::chains::chains_context;
+    use crate::mock_llm::{MockModel, MockStep};
+    use crate::registry_tools::{ListChains, ListTokens};
+    use rig::{agent::AgentBuilder, completion::Prompt};
+    use serde_json::json;
+
+    let anvil = crate::anvil_harness::AnvilHarness::spawn().await?;
 
-    let openai_client = openai::Client::from_url("sk-xxxxx", "https://api.xxxxx.xx/");
+    // Scripted model: answers the prompt with the tool call a live model would make.
+    let model = MockModel::new(vec![MockStep::tool_call(
+        "eth_swap_to_erc20",
+        json!({
+            "chain": anvil.chain,
+            "token_address": anvil.token.to_string(),
+            "amount": "0.1"
+        }),
+    )]);
 
     // Define the agent with the swap tool.
-    let swap_agent = openai_client
-        .agent("Qwen/Qwen2.5-32B-Instruct")
-        .preamble("You are a swap agent here to help the user perform ETH to ERC20 token swaps.")
-        .context(&serde_json::to_string(&*CHAIN_INFOS).unwrap())
+    let swap_agent = AgentBuilder::new(model)
+        .preamble(PREAMBLE)
+        .context(&chains_context())
         .max_tokens(2048)
         .tool(EthSwapToERC20)
+        .tool(ListChains)
+        .tool(ListTokens)
         .build();
 
     // Prompt the agent and print the response.
     println!("Swap ETH to ERC20 token");
-    println!(
-        "Swap Agent: {}",
-        swap_agent.prompt("Swap 0.1 ETH to USDC on base").await?
-    );
+    let response = swap_agent
+        .prompt(&format!("Swap 0.1 ETH to USDC on {}", anvil.chain))
+        .await?;
+    println!("Swap Agent: {}", response);
+    let output: TxOutput = serde_json::from_str(&response)?;
+    assert_eq!(output.status, crate::tx_output::TxStatus::Success);
+    assert_eq!(output.to, local_address());
     Ok(())
 }
+
+// Bad addresses are tool errors the model can correct, not panics.
+#[tokio::test]
+async fn test_invalid_addresses() {
+    let args = SwapArgs {
+        chain: "base".to_string(),
+        token_address: "not-an-address".to_string(),
+        amount: "0.1".to_string(),
+        speed: FeeTier::Normal,
+    };
+    let error = EthSwapToERC20.call(args).await.unwrap_err();
+    assert!(error.message.starts_with("invalid token_address"));
+
+    let mut chain_info = get_chain_info("base").unwrap();
+    chain_info.swap_router = "0x123".to_string();
+    let error = swap_router(&chain_info).unwrap_err();
+    assert!(error.message.starts_with("invalid swap_router on base"));
+
+    assert!(amount_out(&[U256::from(1)]).is_err());
+    assert_eq!(
+        amount_out(&[U256::from(1), U256::from(2)]).unwrap(),
+        U256::from(2)
+    );
+}
@@ -0,0 +1,185 @@
+use std::{
+    fmt::Display,
+    future::{Future, IntoFuture},
+    time::Instant,
+};
+
+use anyhow::Result;
+use sync_wrapper::SyncFuture;
+use tracing::{field, info_span, Instrument};
+use tracing_subscriber::{
+    fmt::{format::FmtSpan, MakeWriter},
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    EnvFilter, Layer,
+};
+
+use crate::metrics;
+
+/// Used when `RUST_LOG` is not set: our spans and events, and warnings from dependencies.
+const DEFAULT_FILTER: &str = "warn,rig_test=info";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Multi-line, human-readable output
+    Pretty,
+    /// One JSON object per line
+    Json,
+}
+
+/// Keeps the OTLP exporter alive; spans still buffered are flushed when it is dropped.
+#[derive(Default)]
+pub struct Telemetry {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::TracerProvider>,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("failed to flush traces: {}", e);
+            }
+        }
+    }
+}
+
+/// Install the global subscriber. Logs go to stderr so they never mix with responses
+/// on stdout; each span logs its duration when it closes.
+pub fn init(format: LogFormat, otlp_endpoint: Option<&str>) -> Result<Telemetry> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer(format, std::io::stderr));
+
+    match otlp_endpoint {
+        None => {
+            registry.try_init()?;
+            Ok(Telemetry::default())
+        }
+        #[cfg(feature = "otlp")]
+        Some(endpoint) => {
+            use opentelemetry::trace::TracerProvider as _;
+
+            let provider = otlp_provider(endpoint)?;
+            let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+            registry
+                .with(tracing_opentelemetry::layer().with_tracer(tracer))
+                .try_init()?;
+            Ok(Telemetry {
+                provider: Some(provider),
+            })
+        }
+        #[cfg(not(feature = "otlp"))]
+        Some(_) => Err(anyhow::anyhow!(
+            "OTLP export needs a build with the `otlp` feature"
+        )),
+    }
+}
+
+fn fmt_layer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_span_events(FmtSpan::CLOSE);
+    match format {
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    }
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_provider(endpoint: &str) -> Result<opentelemetry_sdk::trace::TracerProvider> {
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::{runtime, Resource};
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()?;
+    Ok(opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            env!("CARGO_PKG_NAME"),
+        )]))
+        .build())
+}
+
+/// Run one RPC request in an `rpc` span, recording the error if it fails, and
+/// observe its latency in the metrics.
+///
+/// Alloy's request futures are `Send` but not `Sync`, while rig requires tool
+/// calls to be both. The returned future is `Sync` because it is only ever
+/// polled through `&mut`, so tools can await RPC requests directly.
+pub fn rpc<'a, T, E: Display, F>(
+    chain: &'a str,
+    method: &'static str,
+    request: F,
+) -> impl Future<Output = Result<T, E>> + Send + Sync + 'a
+where
+    F: IntoFuture<Output = Result<T, E>> + Send + 'a,
+    F::IntoFuture: Send,
+{
+    SyncFuture::new(async move {
+        let span = info_span!("rpc", chain, method, error = field::Empty);
+        let started = Instant::now();
+        let result = request.into_future().instrument(span.clone()).await;
+        metrics::rpc_request(chain, method, result.is_ok(), started.elapsed());
+        if let Err(e) = &result {
+            span.record("error", field::display(e));
+        }
+        result
+    })
+}
+
+#[tokio::test]
+async fn test_rpc_span() {
+    use std::sync::{Arc, Mutex};
+
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let writer = {
+        let buffer = buffer.clone();
+        move || BufWriter(buffer.clone())
+    };
+    let subscriber = tracing_subscriber::registry().with(fmt_layer(LogFormat::Json, writer));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    rpc("base", "decimals", async { Ok::<_, String>(18) })
+        .await
+        .unwrap();
+    rpc("base", "send", async {
+        Err::<(), _>("nonce too low".to_string())
+    })
+    .await
+    .unwrap_err();
+
+    let output = String::from_utf8(buffer.lock().unwrap().clone()).unwrap();
+    let spans: Vec<serde_json::Value> = output
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["span"].clone())
+        .collect();
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0]["method"], "decimals");
+    assert!(spans[0].get("error").is_none());
+    assert_eq!(spans[1]["method"], "send");
+    assert_eq!(spans[1]["error"], "nonce too low");
+
+    struct BufWriter(Arc<Mutex<Vec<u8>>>);
+    impl std::io::Write for BufWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::chains::ChainInfo;
+
+/// A token list in the Uniswap token-list schema (https://tokenlists.org).
+#[derive(Debug, Deserialize)]
+pub struct TokenList {
+    pub name: String,
+    pub tokens: Vec<TokenListEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenListEntry {
+    #[serde(rename = "chainId")]
+    pub chain_id: u64,
+    pub address: String,
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    #[serde(rename = "logoURI", default, skip_serializing_if = "Option::is_none")]
+    pub logo_uri: Option<String>,
+}
+
+/// Same symbol already registered on a chain under a different address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenConflict {
+    pub list: String,
+    pub chain: String,
+    pub symbol: String,
+    pub existing_address: String,
+    pub imported_address: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub skipped: usize,
+    pub conflicts: Vec<TokenConflict>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TokenListError {
+    #[error("read token list {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("parse token list {0}: {1}")]
+    Parse(String, serde_json::Error),
+}
+
+pub fn read_token_list(path: &Path) -> Result<TokenList, TokenListError> {
+    let content =
+        fs::read_to_string(path).map_err(|e| TokenListError::Io(path.display().to_string(), e))?;
+    serde_json::from_str(&content).map_err(|e| TokenListError::Parse(path.display().to_string(), e))
+}
+
+/// Merge a token list into the per-chain registry.
+/// Entries for chains that are not configured are skipped. A symbol that is already
+/// mapped to a different address is reported as a conflict and the existing entry wins.
+/// Symbols are compared ignoring case, so "usdc" can't shadow a configured "USDC".
+pub fn merge_token_list(chains: &mut [ChainInfo], list: &TokenList) -> ImportReport {
+    let mut report = ImportReport::default();
+    for entry in &list.tokens {
+        let Some(chain) = chains.iter_mut().find(|c| c.chain_id == entry.chain_id) else {
+            report.skipped += 1;
+            continue;
+        };
+        let existing = chain
+            .tokens
+            .iter()
+            .find(|(symbol, _)| symbol.eq_ignore_ascii_case(&entry.symbol))
+            .map(|(symbol, address)| (symbol.clone(), address.clone()));
+        match existing {
+            Some((symbol, existing)) if existing.eq_ignore_ascii_case(&entry.address) => {
+                chain
+                    .token_metadata
+                    .entry(symbol)
+                    .or_insert_with(|| entry.clone());
+                report.skipped += 1;
+            }
+            Some((_, existing)) => report.conflicts.push(TokenConflict {
+                list: list.name.clone(),
+                chain: chain.chain.clone(),
+                symbol: entry.symbol.clone(),
+                existing_address: existing,
+                imported_address: entry.address.clone(),
+            }),
+            None => {
+                chain
+                    .tokens
+                    .insert(entry.symbol.clone(), entry.address.clone());
+                chain
+                    .token_metadata
+                    .insert(entry.symbol.clone(), entry.clone());
+                report.imported += 1;
+            }
+        }
+    }
+    report
+}
+
+/// Import every `*.json` token list in `dir` into the chain registry.
+pub fn import_token_lists(
+    chains: &mut [ChainInfo],
+    dir: &Path,
+) -> Result<ImportReport, TokenListError> {
+    let mut report = ImportReport::default();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(report),
+        Err(e) => return Err(TokenListError::Io(dir.display().to_string(), e)),
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let list = read_token_list(&path)?;
+        let r = merge_token_list(chains, &list);
+        report.imported += r.imported;
+        report.skipped += r.skipped;
+        report.conflicts.extend(r.conflicts);
+    }
+    Ok(report)
+}
+
+#[test]
+fn test_merge_token_list() {
+    use std::collections::HashMap;
+
+    let mut chains = vec![ChainInfo {
+        chain: "base".to_string(),
+        aliases: vec![],
+        chain_id: 8453,
+        provider_url: String::new(),
+        tokens: HashMap::from([(
+            "USDC".to_string(),
+            "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913".to_string(),
+        )]),
+        token_metadata: HashMap::new(),
+        swap_router: String::new(),
+        explorer_url: None,
+        max_fee_gwei: None,
+        stablecoin: None,
+    }];
+    let list: TokenList = serde_json::from_str(
+        r#"{
+            "name": "test",
+            "tokens": [
+                {"chainId": 8453, "address": "0x833589fcd6edb6e08f4c7c32d4f71b54bda02913", "name": "USD Coin", "symbol": "USDC", "decimals": 6},
+                {"chainId": 8453, "address": "0x50c5725949A6F0c72E6C4a641F24049A917DB0Cb", "name": "Dai", "symbol": "DAI", "decimals": 18, "logoURI": "https://example.com/dai.png"},
+                {"chainId": 8453, "address": "0x0000000000000000000000000000000000000001", "name": "Fake", "symbol": "USDC", "decimals": 6},
+                {"chainId": 8453, "address": "0x0000000000000000000000000000000000000002", "name": "Fake", "symbol": "usdc", "decimals": 6},
+                {"chainId": 8453, "address": "0x50c5725949a6f0c72e6c4a641f24049a917db0cb", "name": "Dai", "symbol": "dai", "decimals": 18},
+                {"chainId": 1, "address": "0x6B175474E89094C44Da98b954EedeAC495271d0F", "name": "Dai", "symbol": "DAI", "decimals": 18}
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    let report = merge_token_list(&mut chains, &list);
+    assert_eq!(report.imported, 1);
+    assert_eq!(report.skipped, 3);
+    assert_eq!(report.conflicts.len(), 2);
+    assert_eq!(report.conflicts[0].symbol, "USDC");
+    assert_eq!(report.conflicts[1].symbol, "usdc");
+    assert!(!chains[0].tokens.contains_key("usdc") && !chains[0].tokens.contains_key("dai"));
+    assert_eq!(
+        chains[0].tokens["DAI"],
+        "0x50c5725949A6F0c72E6C4a641F24049A917DB0Cb"
+    );
+    assert_eq!(chains[0].token_metadata["DAI"].decimals, 18);
+    assert_eq!(chains[0].token_metadata["USDC"].decimals, 6);
+}
@@ -0,0 +1,159 @@
+use alloy::primitives::{
+    utils::{parse_units, ParseUnits},
+    U256,
+};
+use anyhow::{anyhow, Result};
+use schemars::{gen::SchemaGenerator, gen::SchemaSettings, schema::Schema, JsonSchema};
+use serde_json::{json, Value};
+
+use crate::chains::chain_names;
+
+/// Parameters schema for a tool, generated from its `Args` struct with every
+/// subschema inlined, as the model sees it in the tool definition.
+pub fn parameters<T: JsonSchema>() -> Value {
+    let settings = SchemaSettings::draft07().with(|s| {
+        s.inline_subschemas = true;
+        s.option_add_null_type = false;
+        s.meta_schema = None;
+    });
+    let root = settings.into_generator().into_root_schema_for::<T>();
+    let mut schema = serde_json::to_value(root.schema).expect("schema serializes");
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("title");
+        // Providers expect `properties` even when empty; optional fields are simply omitted.
+        let properties = object.entry("properties").or_insert_with(|| json!({}));
+        for property in properties
+            .as_object_mut()
+            .into_iter()
+            .flat_map(|p| p.values_mut())
+        {
+            if property.get("default") == Some(&Value::Null) {
+                property.as_object_mut().map(|p| p.remove("default"));
+            }
+        }
+    }
+    schema
+}
+
+// Field schemas, used with `#[schemars(schema_with = "...")]`. Descriptions come from
+// the field's doc comment.
+
+/// One of the configured chains, by name, alias or chain ID.
+pub fn chain_schema(_: &mut SchemaGenerator) -> Schema {
+    schema(json!({ "type": "string", "enum": chain_names() }))
+}
+
+pub fn address_schema(_: &mut SchemaGenerator) -> Schema {
+    schema(json!({ "type": "string", "pattern": "^0x[0-9a-fA-F]{40}$" }))
+}
+
+pub fn tx_hash_schema(_: &mut SchemaGenerator) -> Schema {
+    schema(json!({ "type": "string", "pattern": "^0x[0-9a-fA-F]{64}$" }))
+}
+
+/// A decimal amount in display units, such as 0.5.
+pub fn amount_schema(_: &mut SchemaGenerator) -> Schema {
+    schema(json!({ "type": "string", "pattern": "^[0-9]+(\\.[0-9]+)?$" }))
+}
+
+/// Base units of a positive display amount, such as "0.5", for a token with
+/// `decimals` decimals. Anything else is an error, never zero.
+pub fn parse_amount(amount: &str, decimals: u8) -> Result<U256> {
+    let fraction = amount.split_once('.').map_or("", |(_, fraction)| fraction);
+    if fraction.len() > decimals as usize {
+        return Err(anyhow!(
+            "amount {} has more than {} decimals",
+            amount,
+            decimals
+        ));
+    }
+    match parse_units(amount, decimals) {
+        Ok(ParseUnits::U256(value)) if !value.is_zero() => Ok(value),
+        Ok(_) => Err(anyhow!("amount {} is not positive", amount)),
+        Err(e) => Err(anyhow!("invalid amount {}: {}", amount, e)),
+    }
+}
+
+/// `amount` whole units of a token with `decimals` decimals, in base units.
+pub fn whole_units(amount: u128, decimals: u8) -> U256 {
+    U256::from(amount) * U256::from(10).pow(U256::from(decimals))
+}
+
+fn schema(value: Value) -> Schema {
+    serde_json::from_value(value).expect("valid schema")
+}
+
+/// Check arguments against a parameters schema, listing every violation.
+pub fn validate(schema: &Value, args: &Value) -> Result<(), String> {
+    let validator = jsonschema::validator_for(schema).map_err(|e| e.to_string())?;
+    let errors: Vec<String> = validator
+        .iter_errors(args)
+        .map(|e| match e.instance_path.to_string() {
+            path if path.is_empty() => e.to_string(),
+            path => format!("{}: {}", path.trim_start_matches('/'), e),
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+#[tokio::test]
+async fn test_validated_tool_args() {
+    use crate::{erc20_transfer::ERC20Transfer, guard::Guarded, registry_tools::ListTokens};
+    use rig::tool::{Tool, ToolSet};
+
+    let toolset = ToolSet::builder()
+        .static_tool(Guarded(ERC20Transfer))
+        .static_tool(Guarded(ListTokens))
+        .build();
+
+    let definition = ERC20Transfer.definition(String::new()).await;
+    let parameters = &definition.parameters;
+    assert_eq!(parameters["additionalProperties"], false);
+    assert_eq!(
+        parameters["required"],
+        json!(["amount", "chain", "to_address", "token_address"])
+    );
+    assert!(parameters["properties"]["chain"]["enum"]
+        .as_array()
+        .unwrap()
+        .contains(&json!("base")));
+    assert!(parameters["properties"]["chain"]["description"].is_string());
+
+    let invalid = [
+        json!({"token_address": "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913", "to_address": "0x1CBd0109c7452926fC7cCf06e73aCC505A296cc7", "amount": "1"}),
+        json!({"chain": "base", "token_address": "0x8335", "to_address": "0x1CBd0109c7452926fC7cCf06e73aCC505A296cc7", "amount": "1"}),
+        json!({"chain": "solana", "token_address": "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913", "to_address": "0x1CBd0109c7452926fC7cCf06e73aCC505A296cc7", "amount": "1"}),
+        json!({"chain": "base", "token_address": "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913", "to_address": "0x1CBd0109c7452926fC7cCf06e73aCC505A296cc7", "amount": "-1"}),
+    ];
+    for args in invalid {
+        let err = toolset
+            .call("erc20_transfer", args.to_string())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid arguments"), "{}", err);
+    }
+
+    let tokens = toolset
+        .call(
+            "list_tokens",
+            json!({"chain": "8453", "symbol": "USDC"}).to_string(),
+        )
+        .await
+        .unwrap();
+    assert!(tokens.contains("0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913"));
+
+    // Fractional amounts are kept, never read as zero.
+    assert_eq!(
+        parse_amount("0.5", 18).unwrap(),
+        U256::from(5 * 10u64.pow(17))
+    );
+    assert_eq!(parse_amount("1.5", 6).unwrap(), U256::from(1_500_000));
+    assert_eq!(whole_units(10, 6), U256::from(10_000_000));
+    for amount in ["0", "0.0", "-1", "1.5.0", "abc", "0.0000001"] {
+        assert!(parse_amount(amount, 6).is_err(), "{}", amount);
+    }
+}
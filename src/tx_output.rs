@@ -0,0 +1,246 @@
+use std::{fmt, time::Duration};
+
+use alloy::{
+    network::{Network, ReceiptResponse},
+    primitives::{Address, Bytes, B256, U256},
+    providers::{PendingTransactionBuilder, Provider},
+    rpc::types::TransactionRequest,
+    transports::Transport,
+};
+use serde::{Deserialize, Serialize};
+use sync_wrapper::SyncFuture;
+
+use crate::{chains::ChainInfo, fee::GasEstimate, telemetry::rpc, wallet::local_address};
+
+/// How long a tool waits for its transaction to be mined before reporting it as pending.
+const RECEIPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxStatus {
+    DryRun,
+    /// Stored as a proposal, sent once it is approved.
+    Proposed,
+    Pending,
+    Success,
+    Reverted,
+}
+
+impl fmt::Display for TxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TxStatus::DryRun => "dry run, not sent",
+            TxStatus::Proposed => "proposed, awaiting approval",
+            TxStatus::Pending => "pending",
+            TxStatus::Success => "success",
+            TxStatus::Reverted => "reverted",
+        })
+    }
+}
+
+/// A broadcast transaction and what its receipt says, if it was mined in time.
+#[derive(Debug, Clone)]
+pub struct Broadcast {
+    pub hash: B256,
+    pub signed_tx: Option<Bytes>,
+    pub status: TxStatus,
+    pub gas_used: Option<u64>,
+    /// The request as signed, with its nonce and fees, for replacing it.
+    pub tx: TransactionRequest,
+}
+
+pub async fn wait_for_receipt<T: Transport + Clone, N: Network>(
+    chain: &str,
+    pending: PendingTransactionBuilder<T, N>,
+    tx: TransactionRequest,
+) -> Broadcast {
+    let hash = *pending.tx_hash();
+    let signed_tx = rpc(
+        chain,
+        "raw_transaction",
+        pending.provider().get_raw_transaction_by_hash(hash),
+    )
+    .await
+    .ok()
+    .flatten();
+    // Waiting for a block is not one request, so it is not timed as one.
+    let receipt = pending.with_timeout(Some(RECEIPT_TIMEOUT)).get_receipt();
+    match SyncFuture::new(receipt).await {
+        Ok(receipt) => Broadcast {
+            hash,
+            signed_tx,
+            status: if receipt.status() {
+                TxStatus::Success
+            } else {
+                TxStatus::Reverted
+            },
+            gas_used: Some(receipt.gas_used() as u64),
+            tx,
+        },
+        // The transaction is out, `/pending` keeps track of it from here.
+        Err(_) => Broadcast {
+            hash,
+            signed_tx,
+            status: TxStatus::Pending,
+            gas_used: None,
+            tx,
+        },
+    }
+}
+
+/// Result of a transaction tool. Serialized as JSON for the LLM and programmatic
+/// callers, and rendered with `Display` for people.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxOutput {
+    /// Tool that produced it, e.g. `eth_transfer`.
+    pub action: String,
+    pub summary: String,
+    pub chain: String,
+    pub chain_id: u64,
+    pub status: TxStatus,
+    pub tx_hash: Option<B256>,
+    /// Raw signed transaction as broadcast.
+    pub signed_tx: Option<Bytes>,
+    pub from: Address,
+    /// Recipient of the funds, the agent's own address for swaps.
+    pub to: Address,
+    /// Token transferred or bought, `None` for ETH transfers.
+    pub token: Option<Address>,
+    /// Amount sent in display units: tokens for ERC20 transfers, ETH otherwise.
+    pub amount: String,
+    /// Amount in base units, unknown for ERC20 dry runs since decimals are read on-chain.
+    pub raw_amount: Option<String>,
+    pub gas_used: Option<u64>,
+    pub explorer_url: Option<String>,
+    /// ID to approve or reject the transaction with, when approval is required.
+    pub proposal_id: Option<String>,
+    /// Expected gas and fee, estimated before sending.
+    pub gas_estimate: Option<GasEstimate>,
+}
+
+impl TxOutput {
+    /// Output of a call that was not sent; `with_broadcast` fills in the rest.
+    pub fn new(
+        action: &str,
+        summary: String,
+        chain_info: &ChainInfo,
+        to: Address,
+        token: Option<Address>,
+        amount: String,
+    ) -> Self {
+        TxOutput {
+            action: action.to_string(),
+            summary,
+            chain: chain_info.chain.clone(),
+            chain_id: chain_info.chain_id,
+            status: TxStatus::DryRun,
+            tx_hash: None,
+            signed_tx: None,
+            from: local_address(),
+            to,
+            token,
+            amount,
+            raw_amount: None,
+            gas_used: None,
+            explorer_url: None,
+            proposal_id: None,
+            gas_estimate: None,
+        }
+    }
+
+    pub fn with_raw_amount(mut self, raw_amount: U256) -> Self {
+        self.raw_amount = Some(raw_amount.to_string());
+        self
+    }
+
+    pub fn with_gas_estimate(mut self, gas_estimate: Option<GasEstimate>) -> Self {
+        self.gas_estimate = gas_estimate;
+        self
+    }
+
+    pub fn with_broadcast(mut self, chain_info: &ChainInfo, broadcast: Broadcast) -> Self {
+        self.status = broadcast.status;
+        self.tx_hash = Some(broadcast.hash);
+        self.signed_tx = broadcast.signed_tx;
+        self.gas_used = broadcast.gas_used;
+        self.explorer_url = chain_info.tx_url(broadcast.hash);
+        self
+    }
+}
+
+impl fmt::Display for TxOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on {}: {}", self.summary, self.chain, self.status)?;
+        if let Some(id) = &self.proposal_id {
+            write!(f, "\n  proposal: {}", id)?;
+        }
+        if let Some(estimate) = &self.gas_estimate {
+            write!(f, "\n  estimated fee: {}", estimate)?;
+        }
+        if let Some(hash) = self.tx_hash {
+            write!(f, "\n  tx: {}", hash)?;
+        }
+        if let Some(gas_used) = self.gas_used {
+            write!(f, "\n  gas used: {}", gas_used)?;
+        }
+        if let Some(url) = &self.explorer_url {
+            write!(f, "\n  explorer: {}", url)?;
+        }
+        Ok(())
+    }
+}
+
+/// Human-readable form of an agent or tool response: transaction outputs are
+/// rendered, anything else is returned as is.
+pub fn render(response: &str) -> String {
+    match serde_json::from_str::<TxOutput>(response) {
+        Ok(output) => output.to_string(),
+        Err(_) => response.to_string(),
+    }
+}
+
+#[test]
+fn test_render_tx_output() {
+    use std::str::FromStr;
+
+    let chain_info = crate::chains::get_chain_info("base").unwrap();
+    let to = Address::from_str("0x1CBd0109c7452926fC7cCf06e73aCC505A296cc7").unwrap();
+    let hash = B256::repeat_byte(0xab);
+    let output = TxOutput::new(
+        "eth_transfer",
+        format!("transfer 1 ETH to {}", to),
+        &chain_info,
+        to,
+        None,
+        "1".to_string(),
+    )
+    .with_raw_amount(U256::from(10u64.pow(18)))
+    .with_gas_estimate(Some(GasEstimate {
+        gas: 21000,
+        fee_wei: "21000000000000".to_string(),
+        fee: "0.000021".to_string(),
+        fee_stable: Some("0.05 USDC".to_string()),
+    }))
+    .with_broadcast(
+        &chain_info,
+        Broadcast {
+            hash,
+            signed_tx: None,
+            status: TxStatus::Success,
+            gas_used: Some(21000),
+            tx: TransactionRequest::default(),
+        },
+    );
+
+    let json = serde_json::to_value(&output).unwrap();
+    assert_eq!(json["status"], "success");
+    assert_eq!(json["chain_id"], 8453);
+    assert_eq!(json["raw_amount"], "1000000000000000000");
+    assert_eq!(json["token"], serde_json::Value::Null);
+
+    let rendered = render(&json.to_string());
+    assert!(rendered.starts_with(&format!("transfer 1 ETH to {} on base: success", to)));
+    assert!(rendered.contains(&format!("explorer: https://basescan.org/tx/{}", hash)));
+    assert!(rendered.contains("estimated fee: ~0.000021 ETH (~0.05 USDC) for 21000 gas"));
+    assert_eq!(render("plain answer"), "plain answer");
+}
@@ -0,0 +1,368 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use alloy::{
+    network::ReceiptResponse,
+    primitives::B256,
+    providers::{Provider, ProviderBuilder},
+    rpc::types::TransactionRequest,
+};
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    chains::{get_chain_info, ChainInfo},
+    metrics,
+    settings::{sent_txs_path, stuck_after},
+    telemetry::rpc,
+    tx_output::{Broadcast, TxStatus},
+    users,
+};
+
+/// A transaction sent by one of the tools, until it is mined or replaced by a
+/// mined one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentTx {
+    pub chain: String,
+    pub hash: B256,
+    pub summary: String,
+    pub sent_at: SystemTime,
+    /// The request as signed, with sender, nonce and fees.
+    pub tx: TransactionRequest,
+    /// API user who sent it, `None` for the local account.
+    pub user: Option<String>,
+    /// A later transaction with the same nonce.
+    pub replaced_by: Option<B256>,
+    /// Whether it was already reported as stuck.
+    reported_stuck: bool,
+}
+
+impl SentTx {
+    // Users only see their own transactions; the CLI sees all of them.
+    fn visible(&self) -> bool {
+        users::current().is_none_or(|user| self.user.as_ref() == Some(&user.name))
+    }
+
+    /// Pending longer than the stuck threshold.
+    pub fn is_stuck(&self) -> bool {
+        self.sent_at.elapsed().unwrap_or_default() >= stuck_after()
+    }
+
+    fn same_nonce(&self, chain: &str, tx: &TransactionRequest) -> bool {
+        self.chain == chain
+            && self.tx.nonce.is_some()
+            && (self.tx.from, self.tx.nonce) == (tx.from, tx.nonce)
+    }
+}
+
+/// Tracked transactions checked against their chains.
+#[derive(Debug, Default)]
+pub struct PendingTxs {
+    /// Still waiting to be mined.
+    pub pending: Vec<SentTx>,
+    /// Not checked because their chain could not be queried, with the error.
+    pub unchecked: Vec<(SentTx, String)>,
+}
+
+/// The tracker every transaction sent by this process is recorded in.
+pub static TX_TRACKER: Lazy<TxTracker> = Lazy::new(|| TxTracker::load(&sent_txs_path()));
+
+/// Transactions sent by the tools, to list the pending ones and watch for stuck ones.
+/// Kept on disk so pending transactions can still be sped up after a restart.
+#[derive(Default)]
+pub struct TxTracker {
+    /// Where the transactions are kept across restarts, `None` to keep them in memory.
+    path: Option<PathBuf>,
+    sent: Mutex<Vec<SentTx>>,
+}
+
+impl TxTracker {
+    pub fn load(path: &Path) -> Self {
+        let sent = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("ignoring sent transactions {}: {}", path.display(), e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        TxTracker {
+            path: Some(path.to_path_buf()),
+            sent: Mutex::new(sent),
+        }
+    }
+
+    fn save(&self, sent: &[SentTx]) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let saved = (|| -> Result<()> {
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, serde_json::to_string_pretty(sent)?)
+                .with_context(|| format!("write sent transactions {}", path.display()))
+        })();
+        if let Err(e) = saved {
+            tracing::error!("failed to save sent transactions: {:#}", e);
+        }
+    }
+
+    /// Track a broadcast transaction. Earlier transactions with the same nonce count
+    /// as replaced by it.
+    pub fn record_sent(&self, chain: &str, broadcast: &Broadcast, summary: String) {
+        let mut sent = self.sent.lock().unwrap();
+        for earlier in sent.iter_mut() {
+            if earlier.replaced_by.is_none() && earlier.same_nonce(chain, &broadcast.tx) {
+                earlier.replaced_by = Some(broadcast.hash);
+            }
+        }
+        sent.push(SentTx {
+            chain: chain.to_string(),
+            hash: broadcast.hash,
+            summary,
+            sent_at: SystemTime::now(),
+            tx: broadcast.tx.clone(),
+            user: users::current().map(|user| user.name.clone()),
+            replaced_by: None,
+            reported_stuck: false,
+        });
+        self.save(&sent);
+    }
+
+    // Stop tracking transactions whose nonce is used up, with those they replaced.
+    fn forget(&self, done: &[SentTx]) {
+        if done.is_empty() {
+            return;
+        }
+        let mut sent = self.sent.lock().unwrap();
+        sent.retain(|tx| {
+            !done
+                .iter()
+                .any(|done| done.hash == tx.hash || done.same_nonce(&tx.chain, &tx.tx))
+        });
+        self.save(&sent);
+    }
+
+    pub fn sent_txs(&self) -> Vec<SentTx> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    // Sent by the current user, on `chain` if given, and not replaced.
+    fn unreplaced(&self, chain: Option<&str>) -> Vec<SentTx> {
+        self.sent_txs()
+            .into_iter()
+            .filter(|tx| tx.visible() && tx.replaced_by.is_none())
+            .filter(|tx| chain.is_none_or(|chain| tx.chain == chain))
+            .collect()
+    }
+
+    /// Sent transactions of the current user, on `chain` if given, that are still
+    /// waiting to be mined: not replaced, without a receipt, and their nonce not
+    /// used up by another transaction. A chain that cannot be queried only leaves
+    /// its own transactions unchecked.
+    pub async fn pending_txs(&self, chain: Option<&str>) -> PendingTxs {
+        let mut txs = PendingTxs::default();
+        let mut done = Vec::new();
+        for tx in self.unreplaced(chain) {
+            match still_pending(&tx).await {
+                Ok(true) => txs.pending.push(tx),
+                Ok(false) => done.push(tx),
+                Err(e) => {
+                    warn!(chain = tx.chain, hash = %tx.hash, "checking transaction failed: {:#}", e);
+                    txs.unchecked.push((tx, format!("{:#}", e)));
+                }
+            }
+        }
+        self.forget(&done);
+        txs
+    }
+
+    /// The pending transaction `hash` on `chain`, if the current user sent it.
+    pub async fn find_pending(&self, chain: &str, hash: B256) -> Result<SentTx> {
+        let not_found = || {
+            anyhow!(
+                "no pending transaction {} on {} sent by this agent",
+                hash,
+                chain
+            )
+        };
+        let sent = self
+            .unreplaced(Some(chain))
+            .into_iter()
+            .find(|tx| tx.hash == hash)
+            .ok_or_else(not_found)?;
+        match still_pending(&sent).await? {
+            true => Ok(sent),
+            false => Err(not_found()),
+        }
+    }
+
+    /// Every `interval`, warn about transactions that became stuck so they can be
+    /// sped up or cancelled.
+    pub async fn watch_stuck(&self, interval: Duration) {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            let pending = self.pending_txs(None).await.pending;
+            for tx in pending
+                .iter()
+                .filter(|tx| tx.is_stuck() && !tx.reported_stuck)
+            {
+                let pending_secs = tx.sent_at.elapsed().unwrap_or_default().as_secs();
+                warn!(chain = tx.chain, hash = %tx.hash, nonce = tx.tx.nonce, pending_secs, summary = tx.summary, "transaction stuck");
+                metrics::stuck_transaction(&tx.chain);
+                let mut sent = self.sent.lock().unwrap();
+                if let Some(tx) = sent.iter_mut().find(|sent| sent.hash == tx.hash) {
+                    tx.reported_stuck = true;
+                }
+                self.save(&sent);
+            }
+        }
+    }
+}
+
+async fn still_pending(sent: &SentTx) -> Result<bool> {
+    let provider_url = get_chain_info(&sent.chain)?.provider_url;
+    let provider = ProviderBuilder::new().on_http(provider_url.parse()?);
+    let receipt = rpc(
+        &sent.chain,
+        "receipt",
+        provider.get_transaction_receipt(sent.hash),
+    )
+    .await?;
+    if receipt.is_some() {
+        return Ok(false);
+    }
+    match (sent.tx.from, sent.tx.nonce) {
+        (Some(from), Some(nonce)) => {
+            let mined = rpc(&sent.chain, "nonce", provider.get_transaction_count(from)).await?;
+            Ok(nonce >= mined)
+        }
+        _ => Ok(true),
+    }
+}
+
+/// Where a transaction stands on its chain right now.
+#[derive(Debug, Clone, Serialize)]
+pub struct TxState {
+    pub chain: String,
+    pub tx_hash: B256,
+    pub status: TxStatus,
+    pub block_number: Option<u64>,
+    pub gas_used: Option<u64>,
+    pub explorer_url: Option<String>,
+}
+
+/// Look a transaction up by hash, `None` when the node does not know it.
+pub async fn tx_state(chain_info: &ChainInfo, hash: B256) -> Result<Option<TxState>> {
+    let provider = ProviderBuilder::new().on_http(chain_info.provider_url.parse()?);
+    let mut state = TxState {
+        chain: chain_info.chain.clone(),
+        tx_hash: hash,
+        status: TxStatus::Pending,
+        block_number: None,
+        gas_used: None,
+        explorer_url: chain_info.tx_url(hash),
+    };
+    match provider.get_transaction_receipt(hash).await? {
+        Some(receipt) => {
+            state.status = if receipt.status() {
+                TxStatus::Success
+            } else {
+                TxStatus::Reverted
+            };
+            state.block_number = receipt.block_number();
+            state.gas_used = Some(receipt.gas_used() as u64);
+        }
+        None if provider.get_transaction_by_hash(hash).await?.is_none() => return Ok(None),
+        None => {}
+    }
+    Ok(Some(state))
+}
+
+#[test]
+fn test_record_replacement() {
+    use alloy::primitives::Address;
+
+    let from = Address::repeat_byte(0x44);
+    let broadcast = |byte, nonce| Broadcast {
+        hash: B256::repeat_byte(byte),
+        signed_tx: None,
+        status: TxStatus::Pending,
+        gas_used: None,
+        tx: TransactionRequest::default().from(from).nonce(nonce),
+    };
+    // Its own tracker, so the transactions never show up in the shared one.
+    let path = std::env::temp_dir().join(format!("rig-eth-sent-{}.json", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let tracker = TxTracker::load(&path);
+    tracker.record_sent("optimism", &broadcast(0x01, 7), "transfer".to_string());
+    tracker.record_sent("optimism", &broadcast(0x02, 8), "swap".to_string());
+    tracker.record_sent(
+        "optimism",
+        &broadcast(0x03, 7),
+        "speed up transfer".to_string(),
+    );
+
+    // A restart still knows what was sent and what replaced what.
+    let sent = TxTracker::load(&path).sent_txs();
+    let replaced = |byte| {
+        sent.iter()
+            .find(|tx| tx.hash == B256::repeat_byte(byte))
+            .unwrap()
+            .replaced_by
+    };
+    assert_eq!(replaced(0x01), Some(B256::repeat_byte(0x03)));
+    assert_eq!(replaced(0x02), None);
+    assert_eq!(replaced(0x03), None);
+
+    // Once nonce 7 is used up, neither transaction with it is tracked anymore.
+    tracker.forget(&sent[2..]);
+    let hashes: Vec<_> = TxTracker::load(&path)
+        .sent_txs()
+        .iter()
+        .map(|tx| tx.hash)
+        .collect();
+    assert_eq!(hashes, [B256::repeat_byte(0x02)]);
+    fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_pending_unchecked() {
+    use alloy::primitives::Address;
+
+    let tx = TransactionRequest::default()
+        .from(Address::repeat_byte(0x45))
+        .nonce(1);
+    let tracker = TxTracker::default();
+    for (chain, byte) in [("nowhere", 0x11), ("elsewhere", 0x12)] {
+        let broadcast = Broadcast {
+            hash: B256::repeat_byte(byte),
+            signed_tx: None,
+            status: TxStatus::Pending,
+            gas_used: None,
+            tx: tx.clone(),
+        };
+        tracker.record_sent(chain, &broadcast, "transfer".to_string());
+    }
+
+    // Neither chain is configured: both are reported, neither fails the other.
+    let txs = tracker.pending_txs(None).await;
+    assert!(txs.pending.is_empty());
+    assert_eq!(txs.unchecked.len(), 2);
+    let txs = tracker.pending_txs(Some("nowhere")).await;
+    assert_eq!(txs.unchecked.len(), 1);
+    assert_eq!(txs.unchecked[0].0.hash, B256::repeat_byte(0x11));
+    assert!(tracker
+        .find_pending("elsewhere", B256::repeat_byte(0x11))
+        .await
+        .unwrap_err()
+        .to_string()
+        .starts_with("no pending transaction"));
+}
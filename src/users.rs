@@ -0,0 +1,476 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use alloy::{
+    primitives::{utils::parse_ether, U256},
+    signers::local::PrivateKeySigner,
+    sol_types::SolCall,
+};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use rig::completion::Message;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{chains::get_chain_info, erc20_transfer::IERC20, settings::spent_path};
+
+/// One entry of the users config. Chains and tools default to all of them, limits to none.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    /// API token, prefer `token_env` outside of local setups.
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Name of the environment variable holding the API token.
+    #[serde(default)]
+    pub token_env: Option<String>,
+    /// Name of the environment variable holding the private key the user signs with.
+    /// Only a single user without limits may leave it unset and use the local account.
+    #[serde(default)]
+    pub private_key_env: Option<String>,
+    #[serde(default)]
+    pub chains: Option<Vec<String>>,
+    #[serde(default)]
+    pub tools: Option<Vec<String>>,
+    /// Most ETH a single transaction may send, e.g. "0.5".
+    #[serde(default)]
+    pub max_tx_value: Option<String>,
+    /// Most ETH sent over any 24 hours.
+    #[serde(default)]
+    pub daily_value_limit: Option<String>,
+}
+
+/// `configs/users.json`: users of the HTTP API by name.
+#[derive(Debug, Deserialize)]
+pub struct UsersConfig {
+    pub users: HashMap<String, UserConfig>,
+}
+
+/// An authenticated user, with what they may do and their conversation.
+/// Not `Debug`, so the token and key never end up in logs.
+pub struct User {
+    pub name: String,
+    token: String,
+    signer: Option<PrivateKeySigner>,
+    /// Canonical chain names, `None` allows every chain.
+    chains: Option<Vec<String>>,
+    tools: Option<Vec<String>>,
+    max_tx_value: Option<U256>,
+    daily_value_limit: Option<U256>,
+    /// ETH sent, for the daily limit.
+    spent: Arc<SpendLog>,
+    pub history: tokio::sync::Mutex<Vec<Message>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PermissionError {
+    #[error("user {user} may not use tool {tool}")]
+    Tool { user: String, tool: String },
+    #[error("user {user} may not use chain {chain}")]
+    Chain { user: String, chain: String },
+    #[error("user {user} may send at most {limit} wei per transaction")]
+    TxValue { user: String, limit: U256 },
+    #[error("user {user} may send at most {limit} wei per day, {spent} wei already sent")]
+    DailyValue {
+        user: String,
+        limit: U256,
+        spent: U256,
+    },
+    #[error("user {user} has spending limits and may not move tokens")]
+    TokenTransfer { user: String },
+}
+
+impl PermissionError {
+    /// Label of the `policy_rejections_total` metric.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            PermissionError::Tool { .. } => "tool_not_permitted",
+            PermissionError::Chain { .. } => "chain_not_permitted",
+            PermissionError::TxValue { .. }
+            | PermissionError::DailyValue { .. }
+            | PermissionError::TokenTransfer { .. } => "spending_limit",
+        }
+    }
+}
+
+/// When and how much ETH was sent.
+type Spent = Vec<(DateTime<Utc>, U256)>;
+
+/// ETH each user sent over the last day, by user name. Kept on disk when the users
+/// are loaded from a file, so a restart does not reset the daily limits.
+#[derive(Default)]
+struct SpendLog {
+    path: Option<PathBuf>,
+    spent: Mutex<HashMap<String, Spent>>,
+}
+
+impl SpendLog {
+    fn load(path: &Path) -> Self {
+        let spent = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("ignoring spent values {}: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        SpendLog {
+            path: Some(path.to_path_buf()),
+            spent: Mutex::new(spent),
+        }
+    }
+
+    fn save(&self, spent: &HashMap<String, Spent>) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let saved = (|| -> Result<()> {
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, serde_json::to_string_pretty(spent)?)
+                .with_context(|| format!("write spent values {}", path.display()))
+        })();
+        if let Err(e) = saved {
+            tracing::error!("failed to save spent values: {:#}", e);
+        }
+    }
+}
+
+impl User {
+    fn from_config(name: &str, config: UserConfig, spent: Arc<SpendLog>) -> Result<Self> {
+        let token = match (config.token, &config.token_env) {
+            (Some(token), None) => token,
+            (None, Some(var)) => env::var(var).map_err(|_| anyhow!("{} is not set", var))?,
+            _ => return Err(anyhow!("user {}: set one of token or token_env", name)),
+        };
+        if token.is_empty() {
+            return Err(anyhow!("user {}: empty token", name));
+        }
+        let signer = match &config.private_key_env {
+            Some(var) => Some(
+                env::var(var)
+                    .map_err(|_| anyhow!("{} is not set", var))?
+                    .parse()
+                    .with_context(|| format!("user {}: parse {}", name, var))?,
+            ),
+            None => None,
+        };
+        let chains = match config.chains {
+            Some(chains) => Some(
+                chains
+                    .iter()
+                    .map(|c| get_chain_info(c).map(|info| info.chain))
+                    .collect::<Result<_, _>>()
+                    .with_context(|| format!("user {}", name))?,
+            ),
+            None => None,
+        };
+        let ether = |value: Option<String>| {
+            value
+                .map(|v| parse_ether(&v).with_context(|| format!("user {}: limit {}", name, v)))
+                .transpose()
+        };
+        Ok(User {
+            name: name.to_string(),
+            token,
+            signer,
+            chains,
+            tools: config.tools,
+            max_tx_value: ether(config.max_tx_value)?,
+            daily_value_limit: ether(config.daily_value_limit)?,
+            spent,
+            history: Default::default(),
+        })
+    }
+
+    /// The key the user's transactions are signed with, if not the local account.
+    pub fn signer(&self) -> Option<&PrivateKeySigner> {
+        self.signer.as_ref()
+    }
+
+    fn has_limits(&self) -> bool {
+        self.max_tx_value.is_some() || self.daily_value_limit.is_some()
+    }
+
+    pub fn allows_tool(&self, tool: &str) -> bool {
+        self.tools
+            .as_ref()
+            .is_none_or(|tools| tools.iter().any(|t| t == tool))
+    }
+
+    pub fn check_tool(&self, tool: &str) -> Result<(), PermissionError> {
+        if self.allows_tool(tool) {
+            return Ok(());
+        }
+        Err(PermissionError::Tool {
+            user: self.name.clone(),
+            tool: tool.to_string(),
+        })
+    }
+
+    /// Unknown chains pass, the tool reports them.
+    pub fn check_chain(&self, chain: &str) -> Result<(), PermissionError> {
+        let (Some(chains), Ok(info)) = (&self.chains, get_chain_info(chain)) else {
+            return Ok(());
+        };
+        if chains.contains(&info.chain) {
+            return Ok(());
+        }
+        Err(PermissionError::Chain {
+            user: self.name.clone(),
+            chain: info.chain,
+        })
+    }
+
+    /// The limits are in ETH and can't price tokens, so users with limits may not
+    /// call `input`s that transfer or approve ERC20 tokens.
+    pub fn check_token_transfer(&self, input: &[u8]) -> Result<(), PermissionError> {
+        if !self.has_limits() || !moves_tokens(input) {
+            return Ok(());
+        }
+        Err(PermissionError::TokenTransfer {
+            user: self.name.clone(),
+        })
+    }
+
+    /// Count `value` against the limits before sending it. Give it back with
+    /// [`User::refund`] if the transaction is not sent.
+    pub fn reserve(&self, value: U256) -> Result<DateTime<Utc>, PermissionError> {
+        if let Some(limit) = self.max_tx_value.filter(|limit| value > *limit) {
+            return Err(PermissionError::TxValue {
+                user: self.name.clone(),
+                limit,
+            });
+        }
+        let now = Utc::now();
+        let mut log = self.spent.spent.lock().unwrap();
+        let spent = log.entry(self.name.clone()).or_default();
+        spent.retain(|(at, _)| *at > now - Duration::days(1));
+        let total = spent.iter().fold(U256::ZERO, |sum, (_, v)| sum + v);
+        if let Some(limit) = self
+            .daily_value_limit
+            .filter(|limit| total.saturating_add(value) > *limit)
+        {
+            return Err(PermissionError::DailyValue {
+                user: self.name.clone(),
+                limit,
+                spent: total,
+            });
+        }
+        spent.push((now, value));
+        self.spent.save(&log);
+        Ok(now)
+    }
+
+    pub fn refund(&self, reserved_at: DateTime<Utc>) {
+        let mut log = self.spent.spent.lock().unwrap();
+        let Some(spent) = log.get_mut(&self.name) else {
+            return;
+        };
+        if let Some(i) = spent.iter().position(|(at, _)| *at == reserved_at) {
+            spent.remove(i);
+            self.spent.save(&log);
+        }
+    }
+}
+
+/// Users of the HTTP API, looked up by token.
+#[derive(Default)]
+pub struct Users {
+    users: Vec<Arc<User>>,
+}
+
+impl Users {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("read users config {}", path.display()))?;
+        let config: UsersConfig = serde_json::from_str(&content)
+            .with_context(|| format!("parse users config {}", path.display()))?;
+        Users::new(config, SpendLog::load(&spent_path()))
+    }
+
+    /// Users whose spending is only counted in memory.
+    #[cfg(test)]
+    pub fn from_config(config: UsersConfig) -> Result<Self> {
+        Users::new(config, SpendLog::default())
+    }
+
+    fn new(config: UsersConfig, spent: SpendLog) -> Result<Self> {
+        let spent = Arc::new(spent);
+        let mut users = config
+            .users
+            .into_iter()
+            .map(|(name, config)| User::from_config(&name, config, spent.clone()).map(Arc::new))
+            .collect::<Result<Vec<_>>>()?;
+        users.sort_by(|a, b| a.name.cmp(&b.name));
+        // On the shared local account users spend each other's funds and nonces,
+        // and a limit is no limit.
+        let shared = users.len() > 1;
+        if let Some(user) = users
+            .iter()
+            .find(|user| user.signer.is_none() && (shared || user.has_limits()))
+        {
+            return Err(anyhow!(
+                "user {}: set private_key_env, users with limits or next to other users \
+                 may not sign with the local account",
+                user.name
+            ));
+        }
+        let mut tokens = HashSet::new();
+        if let Some(user) = users.iter().find(|user| !tokens.insert(&user.token)) {
+            return Err(anyhow!(
+                "user {} shares a token with another user",
+                user.name
+            ));
+        }
+        Ok(Users { users })
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<User>> {
+        self.users.iter().find(|user| user.name == name).cloned()
+    }
+
+    pub fn authenticate(&self, token: &str) -> Option<Arc<User>> {
+        self.users
+            .iter()
+            .find(|user| constant_time_eq(user.token.as_bytes(), token.as_bytes()))
+            .cloned()
+    }
+}
+
+fn moves_tokens(input: &[u8]) -> bool {
+    [
+        IERC20::transferCall::SELECTOR,
+        IERC20::transferFromCall::SELECTOR,
+        IERC20::approveCall::SELECTOR,
+    ]
+    .iter()
+    .any(|selector| input.starts_with(selector))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+tokio::task_local! {
+    static CURRENT: Arc<User>;
+}
+
+/// Run `f` on behalf of `user`: tools check their permissions, sign with their
+/// account and count what they send against their limits.
+pub async fn scope<F: Future>(user: Arc<User>, f: F) -> F::Output {
+    CURRENT.scope(user, f).await
+}
+
+/// The user of the surrounding [`scope`], `None` outside of one (CLI, open HTTP API).
+pub fn current() -> Option<Arc<User>> {
+    CURRENT.try_with(Arc::clone).ok()
+}
+
+/// Put a throwaway key in `RIG_ETH_TEST_KEY` for test users to sign with.
+#[cfg(test)]
+pub fn set_test_key() {
+    static SET: std::sync::Once = std::sync::Once::new();
+    SET.call_once(|| {
+        env::set_var(
+            "RIG_ETH_TEST_KEY",
+            "0x59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d",
+        )
+    });
+}
+
+#[tokio::test]
+async fn test_user_permissions() {
+    set_test_key();
+    let config = || -> UsersConfig {
+        serde_json::from_str(
+            r#"{
+                "users": {
+                    "alice": {"token": "a-token", "chains": ["Base"], "tools": ["get_balance"],
+                              "max_tx_value": "1", "daily_value_limit": "1.5",
+                              "private_key_env": "RIG_ETH_TEST_KEY"},
+                    "bob": {"token": "b-token", "private_key_env": "RIG_ETH_TEST_KEY"}
+                }
+            }"#,
+        )
+        .unwrap()
+    };
+    let users = Users::from_config(config()).unwrap();
+    assert!(users.authenticate("c-token").is_none());
+    let bob = users.authenticate("b-token").unwrap();
+    assert!(bob.check_tool("eth_transfer").is_ok() && bob.check_chain("arbitrum").is_ok());
+
+    let alice = users.authenticate("a-token").unwrap();
+    assert_eq!(alice.name, "alice");
+    assert!(alice.check_tool("get_balance").is_ok());
+    assert!(alice.check_tool("eth_transfer").is_err());
+    assert!(alice.check_chain("8453").is_ok());
+    assert!(matches!(
+        alice.check_chain("arbitrum"),
+        Err(PermissionError::Chain { .. })
+    ));
+
+    let one = parse_ether("1").unwrap();
+    assert!(matches!(
+        alice.reserve(parse_ether("2").unwrap()),
+        Err(PermissionError::TxValue { .. })
+    ));
+    let first = alice.reserve(one).unwrap();
+    assert!(matches!(
+        alice.reserve(one),
+        Err(PermissionError::DailyValue { .. })
+    ));
+    alice.refund(first);
+    assert!(alice.reserve(one).is_ok());
+
+    // What was sent still counts after a restart.
+    let path = std::env::temp_dir().join(format!("rig-eth-spent-{}.json", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let users = Users::new(config(), SpendLog::load(&path)).unwrap();
+    users.get("alice").unwrap().reserve(one).unwrap();
+    let restarted = Users::new(config(), SpendLog::load(&path)).unwrap();
+    assert!(matches!(
+        restarted.get("alice").unwrap().reserve(one),
+        Err(PermissionError::DailyValue { .. })
+    ));
+    fs::remove_file(&path).unwrap();
+
+    // Limits, or other users, need an account of one's own.
+    for users in [
+        r#"{"users": {"alice": {"token": "a-token", "max_tx_value": "1"}}}"#,
+        r#"{"users": {"alice": {"token": "a-token"}, "bob": {"token": "b-token",
+                      "private_key_env": "RIG_ETH_TEST_KEY"}}}"#,
+    ] {
+        let Err(error) = Users::from_config(serde_json::from_str(users).unwrap()) else {
+            panic!("accepted {}", users);
+        };
+        assert!(error
+            .to_string()
+            .starts_with("user alice: set private_key_env"));
+    }
+    assert!(Users::from_config(
+        serde_json::from_str(r#"{"users": {"alice": {"token": "a-token"}}}"#).unwrap()
+    )
+    .is_ok());
+
+    // ETH limits can't price tokens: limited users may not move them at all.
+    let transfer = IERC20::transferCall {
+        to: Default::default(),
+        amount: U256::from(1),
+    }
+    .abi_encode();
+    assert!(matches!(
+        alice.check_token_transfer(&transfer),
+        Err(PermissionError::TokenTransfer { .. })
+    ));
+    assert!(alice.check_token_transfer(&[]).is_ok());
+    assert!(bob.check_token_transfer(&transfer).is_ok());
+
+    assert!(scope(alice, async { current().unwrap().name == "alice" }).await);
+    assert!(current().is_none());
+}
@@ -0,0 +1,223 @@
+use alloy::{
+    network::EthereumWallet,
+    primitives::Address,
+    providers::{Provider, ProviderBuilder, RootProvider},
+    rpc::types::TransactionRequest,
+    signers::local::PrivateKeySigner,
+    transports::http::{Client, Http},
+};
+use anyhow::{anyhow, Result};
+use tracing::warn;
+
+use crate::{
+    chains::ChainInfo,
+    fee::{suggest_fees, suggest_replacement_fees, FeeTier},
+    metrics,
+    nonce::{is_nonce_error, OnChainNonces, NONCES},
+    telemetry::rpc,
+    tx_output::{wait_for_receipt, Broadcast, TxStatus},
+    users,
+};
+
+/// The account the tools sign with: the current user's, or the local account.
+pub fn local_signer() -> PrivateKeySigner {
+    if let Some(signer) = users::current().and_then(|user| user.signer().cloned()) {
+        return signer;
+    }
+    // Read the private key from the environment variable
+    // let private_key = env::var("PRIVATE_KEY").unwrap();
+
+    // [RISK WARNING! Writing a private key in the code file is insecure behavior.]
+    // The following code is for testing only. Set up signer from private key, be aware of danger.
+    let private_key = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    private_key.parse().expect("parse PrivateKeySigner")
+}
+
+pub fn local_address() -> Address {
+    local_signer().address()
+}
+
+/// Sign `tx` with the local account, paying fees for `speed` within the chain's cap
+/// and filling in nonce, gas limit and chain ID, broadcast it and wait for the
+/// receipt. The nonce comes from the shared nonce manager, unless `tx` already
+/// has one: then it replaces the pending transaction with that nonce and pays
+/// enough more for nodes to accept it. The value counts against the current
+/// user's limits, replacements included: their value need not match the
+/// original's, so counting both errs on the safe side.
+pub async fn send_transaction(
+    chain_info: &ChainInfo,
+    tx: TransactionRequest,
+    speed: FeeTier,
+) -> Result<Broadcast> {
+    let user = users::current();
+    let reserved = match &user {
+        Some(user) => Some(
+            user.check_token_transfer(tx.input.input().map_or(&[][..], |input| input))
+                .and_then(|_| user.reserve(tx.value.unwrap_or_default()))
+                .inspect_err(|e| metrics::policy_rejection("send_transaction", e.reason()))?,
+        ),
+        None => None,
+    };
+    let result = sign_and_send(chain_info, tx, speed).await;
+    if let (Err(_), Some(user), Some(reserved)) = (&result, user, reserved) {
+        user.refund(reserved);
+    }
+    result
+}
+
+async fn sign_and_send(
+    chain_info: &ChainInfo,
+    mut tx: TransactionRequest,
+    speed: FeeTier,
+) -> Result<Broadcast> {
+    let chain = chain_info.chain.clone();
+    let account = local_signer();
+    let from = account.address();
+    let wallet: EthereumWallet = EthereumWallet::from(account);
+    tx.from = Some(from);
+
+    // Create a http client to the EVM chain network.
+    let provider: RootProvider<Http<Client>> =
+        ProviderBuilder::new().on_http(chain_info.provider_url.parse()?);
+
+    let max_fee = chain_info.max_fee_per_gas()?;
+
+    // Create eth signer.
+    let signer = ProviderBuilder::new()
+        .with_recommended_fillers()
+        .wallet(wallet)
+        .on_provider(provider);
+
+    if tx.nonce.is_some() {
+        // Replacing a transaction: the nonce is already taken.
+        suggest_replacement_fees(&signer, &chain, speed, max_fee, &tx)
+            .await?
+            .apply(&mut tx);
+        let pending = rpc(&chain, "send", signer.send_transaction(tx.clone()))
+            .await
+            .map_err(|e| anyhow!(format!("alloy rpc error: {}", e)))?;
+        return Ok(wait_for_receipt(&chain, pending, tx).await);
+    }
+    suggest_fees(&signer, &chain, speed, max_fee)
+        .await?
+        .apply(&mut tx);
+    let mut resynced = false;
+    loop {
+        let on_chain = OnChainNonces {
+            mined: rpc(&chain, "nonce", signer.get_transaction_count(from))
+                .await
+                .map_err(|e| anyhow!(format!("alloy rpc error: {}", e)))?,
+            pending: rpc(
+                &chain,
+                "nonce",
+                signer.get_transaction_count(from).pending(),
+            )
+            .await
+            .map_err(|e| anyhow!(format!("alloy rpc error: {}", e)))?,
+        };
+        let nonce = NONCES.allocate(&chain, from, on_chain);
+        tx.nonce = Some(nonce);
+        // Send the transaction and listen for the transaction to be included.
+        match rpc(&chain, "send", signer.send_transaction(tx.clone())).await {
+            Ok(pending) => {
+                let broadcast = wait_for_receipt(&chain, pending, tx).await;
+                // A transaction still pending keeps its nonce until it is mined.
+                if broadcast.status != TxStatus::Pending {
+                    NONCES.confirm(&chain, from, nonce);
+                }
+                return Ok(broadcast);
+            }
+            Err(e) if !resynced && is_nonce_error(&e.to_string()) => {
+                warn!(%chain, nonce, error = %e, "nonce rejected, retrying");
+                NONCES.resync(&chain, from);
+                resynced = true;
+            }
+            Err(e) => {
+                NONCES.release(&chain, from, nonce);
+                return Err(anyhow!(format!("alloy rpc error: {}", e))); // sign_transaction
+            }
+        }
+    }
+}
+
+// Limits are checked before anything reaches the node.
+#[tokio::test]
+async fn test_send_checks_limits() -> Result<()> {
+    use alloy::{
+        network::TransactionBuilder,
+        primitives::{utils::parse_ether, U256},
+        sol_types::SolCall,
+    };
+
+    use crate::{erc20_transfer::IERC20, users::Users};
+
+    users::set_test_key();
+    let users = Users::from_config(serde_json::from_str(
+        r#"{"users": {"alice": {"token": "a-token", "max_tx_value": "1",
+                                "private_key_env": "RIG_ETH_TEST_KEY"}}}"#,
+    )?)?;
+    let alice = users.authenticate("a-token").unwrap();
+    let chain_info = crate::chains::get_chain_info("base")?;
+    let to_address = Address::repeat_byte(0x77);
+
+    let token_transfer = TransactionRequest::default()
+        .with_to(Address::repeat_byte(0x55))
+        .with_input(
+            IERC20::transferCall {
+                to: to_address,
+                amount: U256::MAX,
+            }
+            .abi_encode(),
+        );
+    let replacement = TransactionRequest::default()
+        .with_to(to_address)
+        .with_value(parse_ether("2")?)
+        .with_nonce(7);
+    for tx in [token_transfer, replacement] {
+        let sent = users::scope(
+            alice.clone(),
+            send_transaction(&chain_info, tx, FeeTier::Normal),
+        )
+        .await;
+        let error = sent.unwrap_err();
+        assert!(
+            error.downcast_ref::<users::PermissionError>().is_some(),
+            "{error}"
+        );
+    }
+    Ok(())
+}
+
+// Two transfers from one account at once, through the path every transaction tool takes.
+#[cfg(test)]
+async fn send_concurrently() -> Result<()> {
+    use alloy::{network::TransactionBuilder, primitives::U256};
+
+    let anvil = crate::anvil_harness::AnvilHarness::spawn().await?;
+    let chain_info = crate::chains::get_chain_info(&anvil.chain)?;
+    let to_address = Address::repeat_byte(0x77);
+    let transfer = || {
+        TransactionRequest::default()
+            .with_to(to_address)
+            .with_value(U256::from(1000))
+    };
+    let (first, second) = tokio::try_join!(
+        send_transaction(&chain_info, transfer(), FeeTier::Normal),
+        send_transaction(&chain_info, transfer(), FeeTier::Fast),
+    )?;
+    assert_eq!(first.status, TxStatus::Success);
+    assert_eq!(second.status, TxStatus::Success);
+    assert_ne!(first.tx.nonce, second.tx.nonce);
+    assert_eq!(anvil.eth_balance(to_address).await?, U256::from(2000));
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_send_current_thread() -> Result<()> {
+    send_concurrently().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_send_multi_thread() -> Result<()> {
+    send_concurrently().await
+}
@@ -0,0 +1,17 @@
+#!/bin/sh
+# Downloads the compiled Uniswap V2 contracts the anvil test harness deploys,
+# from the build directories of the published npm packages. The router computes
+# pair addresses from the init code hash of this exact UniswapV2Pair build, so
+# the factory and router must come from these releases.
+set -eu
+
+dir=$(cd "$(dirname "$0")" && pwd)
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+cd "$tmp"
+npm pack --silent @uniswap/v2-core@1.0.1 @uniswap/v2-periphery@1.1.0-beta.0 >/dev/null
+tar -xzf uniswap-v2-core-1.0.1.tgz package/build/UniswapV2Factory.json
+tar -xzf uniswap-v2-periphery-1.1.0-beta.0.tgz \
+    package/build/UniswapV2Router02.json package/build/WETH9.json package/build/ERC20.json
+cp package/build/*.json "$dir"